@@ -0,0 +1,153 @@
+//! A `posix_memalign`/`free`-managed buffer for DMA transfers
+
+use crate::{error::AllocError, heap::Heap, tryclone::TryClone};
+use core::{
+    fmt::{self, Debug, Formatter},
+    mem,
+    ops::{Deref, DerefMut},
+};
+
+/// A heap-allocated buffer with a power-of-two alignment that is suitable for DMA transfers
+///
+/// # Ring mode
+/// The RP2040 DMA ring mode wraps the lower `n` bits of the read or write address, which requires a buffer that is
+/// exactly `2^n` bytes large and aligned to its size. `DmaBuffer::new` aligns the buffer to its size rounded up to the
+/// next power of two if it does not exceed [`DmaBuffer::MAX_RING_SIZE`], so a buffer with a power-of-two size can be
+/// used in ring mode as-is. Larger buffers are only aligned to the DMA word size.
+pub struct DmaBuffer<T>
+where
+    T: Copy,
+{
+    /// The underlying memory
+    memory: Heap<[T]>,
+    /// The alignment of the memory
+    align: usize,
+}
+impl<T> DmaBuffer<T>
+where
+    T: Copy,
+{
+    /// The largest ring size that is supported by the DMA ring mode in bytes
+    pub const MAX_RING_SIZE: usize = 1 << 15;
+    /// The alignment of a DMA word in bytes
+    pub const WORD_ALIGN: usize = 4;
+
+    /// Creates a new buffer with `len` copies of `value`
+    ///
+    /// # Note
+    /// If the buffer is not larger than [`DmaBuffer::MAX_RING_SIZE`], it is aligned to its size rounded up to the next
+    /// power of two for the ring mode. Otherwise, it is only aligned to [`DmaBuffer::WORD_ALIGN`], since the ring mode
+    /// cannot be used anyway and a larger alignment would waste a lot of memory.
+    pub fn new(len: usize, value: T) -> Result<Self, AllocError> {
+        let size = mem::size_of::<T>().checked_mul(len).ok_or(AllocError)?;
+        let align = match size <= Self::MAX_RING_SIZE {
+            true => size.next_power_of_two(),
+            false => Self::WORD_ALIGN,
+        };
+        Self::new_aligned(len, value, align)
+    }
+    /// Creates a new buffer with `len` copies of `value` and an alignment of at least `align` bytes
+    ///
+    /// # Panics
+    /// This function panics if `align` is not a power of two.
+    pub fn new_aligned(len: usize, value: T, align: usize) -> Result<Self, AllocError> {
+        // Allocate and initialize the memory
+        let mut memory = Heap::new_uninit_slice_aligned(len, align).ok_or(AllocError)?;
+        memory.iter_mut().for_each(|slot| {
+            slot.write(value);
+        });
+
+        // The effective alignment also respects the alignment of `T`
+        let align = align.max(mem::align_of::<T>());
+        Ok(Self { memory: unsafe { memory.assume_init() }, align })
+    }
+
+    /// The amount of elements
+    pub fn len(&self) -> usize {
+        self.memory.len()
+    }
+    /// Whether the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty()
+    }
+    /// The guaranteed alignment of the buffer in bytes
+    pub const fn align(&self) -> usize {
+        self.align
+    }
+    /// A raw pointer to the first element, e.g. to configure the DMA read or write address
+    pub fn as_ptr(&self) -> *const T {
+        self.memory.as_ptr()
+    }
+    /// A mutable raw pointer to the first element, e.g. to configure the DMA write address
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.memory.as_mut_ptr()
+    }
+
+    /// The ring size of the buffer as amount of wrapped address bits for the DMA ring mode, or `None` if the buffer
+    /// cannot be used in ring mode
+    ///
+    /// # Note
+    /// A buffer can be used in ring mode if its size in bytes is a power of two between 2 and
+    /// [`DmaBuffer::MAX_RING_SIZE`] and if it is aligned to its size.
+    pub fn ring_size_bits(&self) -> Option<u32> {
+        let size = mem::size_of_val(self.memory.inner());
+        let is_ring = size.is_power_of_two() && (2..=Self::MAX_RING_SIZE).contains(&size) && self.align >= size;
+        is_ring.then(|| size.trailing_zeros())
+    }
+}
+impl<T> TryClone for DmaBuffer<T>
+where
+    T: Copy,
+{
+    /// Creates a copy of the buffer with the same alignment
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        // Allocate and initialize the memory
+        let mut memory = Heap::new_uninit_slice_aligned(self.len(), self.align).ok_or(AllocError)?;
+        memory.iter_mut().zip(self.iter()).for_each(|(slot, value)| {
+            slot.write(*value);
+        });
+        Ok(Self { memory: unsafe { memory.assume_init() }, align: self.align })
+    }
+}
+impl<T> Deref for DmaBuffer<T>
+where
+    T: Copy,
+{
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.memory
+    }
+}
+impl<T> DerefMut for DmaBuffer<T>
+where
+    T: Copy,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.memory
+    }
+}
+impl<T> AsRef<[T]> for DmaBuffer<T>
+where
+    T: Copy,
+{
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+impl<T> AsMut<[T]> for DmaBuffer<T>
+where
+    T: Copy,
+{
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+impl<T> Debug for DmaBuffer<T>
+where
+    T: Copy + Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("DmaBuffer").field("align", &self.align).field("memory", &&*self.memory).finish()
+    }
+}
@@ -0,0 +1,46 @@
+//! Crate-specific error types
+
+use core::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// The memory allocation failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AllocError;
+impl Display for AllocError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("failed to allocate memory")
+    }
+}
+impl Error for AllocError {}
+
+/// A reference count would overflow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RefCountOverflow;
+impl Display for RefCountOverflow {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("reference count overflow")
+    }
+}
+impl Error for RefCountOverflow {}
+
+/// The value is already mutably borrowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BorrowError;
+impl Display for BorrowError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("already mutably borrowed")
+    }
+}
+impl Error for BorrowError {}
+
+/// The value is already borrowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BorrowMutError;
+impl Display for BorrowMutError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("already borrowed")
+    }
+}
+impl Error for BorrowMutError {}
@@ -1,24 +1,110 @@
 //! A `malloc`/`free`-managed heap object
 
-use crate::trace;
+use crate::{error::AllocError, trace, tryclone::TryClone};
 use core::{
+    borrow::{Borrow, BorrowMut},
+    cmp::Ordering,
+    error::Error,
     ffi::c_void,
-    fmt::{self, Debug, Formatter},
+    fmt::{self, Debug, Display, Formatter, Pointer},
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
     mem::{self, MaybeUninit},
     ops::{Deref, DerefMut},
+    ptr, str,
 };
 
-// Bindings to `malloc` and `free`
-extern "C" {
-    /// Allocates some memory
-    fn malloc(size: usize) -> *mut c_void;
-    /// Frees some allocated memory
-    fn free(ptr: *mut c_void);
+/// The alignment that `malloc` guarantees on all supported targets
+pub(crate) const MALLOC_ALIGN: usize = 8;
+
+// The backend that serves the allocations of this crate
+#[cfg(not(feature = "slab"))]
+use self::ffi as backend;
+#[cfg(feature = "slab")]
+use crate::slab::backend;
+
+/// Bindings to `malloc`, `realloc`, `posix_memalign` and `free`
+pub(crate) mod ffi {
+    use core::ffi::c_void;
+
+    extern "C" {
+        /// Allocates some memory
+        pub fn malloc(size: usize) -> *mut c_void;
+        /// Resizes some allocated memory, which may move the memory to a new location
+        pub fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void;
+        /// Frees some allocated memory
+        pub fn free(ptr: *mut c_void);
+        /// Allocates some memory with the given power-of-two alignment, which can be released via `free`
+        pub fn posix_memalign(memptr: *mut *mut c_void, align: usize, size: usize) -> i32;
+    }
+}
+
+/// Allocates `size` bytes with an alignment of at least `align` bytes
+///
+/// # Panics
+/// This function panics if `align` is not a power of two.
+pub(crate) fn malloc(size: usize, align: usize) -> *mut c_void {
+    assert!(align.is_power_of_two(), "alignment is not a power of two");
+    match align <= MALLOC_ALIGN {
+        true => unsafe { backend::malloc(size) },
+        false => malloc_aligned(size, align),
+    }
+}
+/// Resizes some allocated memory from `old_size` to `size` bytes with an alignment of at least `align` bytes, which may
+/// move the memory to a new location
+///
+/// # Safety
+/// `memory` must have been allocated via `malloc` or `realloc` with at least `old_size` bytes and the same alignment. If
+/// the resizing fails, a null pointer is returned and the memory is left unchanged.
+pub(crate) unsafe fn realloc(memory: *mut c_void, old_size: usize, size: usize, align: usize) -> *mut c_void {
+    // Use `realloc` if it preserves the alignment
+    if align <= MALLOC_ALIGN {
+        return backend::realloc(memory, size);
+    }
+
+    // Move the memory manually otherwise
+    let resized = malloc_aligned(size, align);
+    if !resized.is_null() {
+        ptr::copy_nonoverlapping(memory as *const u8, resized as *mut u8, old_size.min(size));
+        backend::free(memory);
+    }
+    resized
+}
+/// Frees some allocated memory
+///
+/// # Safety
+/// `memory` must have been allocated via `malloc` or `realloc`.
+pub(crate) unsafe fn free(memory: *mut c_void) {
+    backend::free(memory)
+}
+
+/// Allocates `size` bytes with an alignment of at least `align` bytes via `posix_memalign`
+pub(crate) fn malloc_aligned(size: usize, align: usize) -> *mut c_void {
+    // `posix_memalign` requires the alignment to be a multiple of the pointer size, and may return a null pointer for
+    // zero bytes
+    let align = align.max(mem::size_of::<*mut c_void>());
+    let mut memory = ptr::null_mut();
+    match unsafe { ffi::posix_memalign(&mut memory, align, size.max(1)) } {
+        // Memory that is aligned by `posix_memalign` can be released by all `free` paths, including the slab allocator's
+        0 => memory,
+        _ => ptr::null_mut(),
+    }
 }
 
 /// A `malloc`/`free`-managed heap object
+///
+/// # Trait objects and other unsized types
+/// Since `CoerceUnsized` is not available on stable Rust, a `Heap<T>` cannot be coerced into a `Heap<dyn Trait>`
+/// implicitly. Use the [`heap_dyn!`](crate::heap_dyn) macro or [`Heap::unsize`] instead.
+///
+/// # Closures
+/// The `Fn*`-traits cannot be implemented on stable Rust. However, since call expressions dereference the callee
+/// automatically, a `Heap<dyn FnMut()>` can be called like a plain closure anyway.
 #[repr(transparent)]
-pub struct Heap<T> {
+pub struct Heap<T>
+where
+    T: ?Sized,
+{
     /// The heap pointer
     memory: *mut T,
 }
@@ -26,7 +112,26 @@ impl<T> Heap<MaybeUninit<T>> {
     /// Creates a new uninitialized array
     pub fn new_uninit() -> Option<Self> {
         // Allocate the memory
-        let memory = unsafe { malloc(Self::SIZE) as *mut MaybeUninit<T> };
+        let memory = malloc(Self::SIZE, mem::align_of::<T>()) as *mut MaybeUninit<T>;
+        if memory.is_null() {
+            return None;
+        }
+
+        // Trace the memory
+        unsafe { trace::increment_allocated(Self::SIZE) };
+        Some(Self { memory })
+    }
+    /// Creates a new uninitialized array with an alignment of at least `align` bytes
+    ///
+    /// # Note
+    /// Operations that resize the memory via `realloc` (e.g. `Heap::map_in_place`) may not preserve the alignment.
+    ///
+    /// # Panics
+    /// This function panics if `align` is not a power of two.
+    pub fn new_uninit_aligned(align: usize) -> Option<Self> {
+        // Allocate the memory
+        let align = align.max(mem::align_of::<T>());
+        let memory = malloc(Self::SIZE, align) as *mut MaybeUninit<T>;
         if memory.is_null() {
             return None;
         }
@@ -51,6 +156,73 @@ impl<T> Heap<MaybeUninit<T>> {
         Heap { memory: memory.cast() }
     }
 }
+impl<T> Heap<[MaybeUninit<T>]> {
+    /// Creates a new uninitialized slice with `len` elements
+    pub fn new_uninit_slice(len: usize) -> Option<Self> {
+        // Allocate the memory
+        let size = mem::size_of::<T>().checked_mul(len)?;
+        let memory = malloc(size, mem::align_of::<T>()) as *mut MaybeUninit<T>;
+        if memory.is_null() {
+            return None;
+        }
+
+        // Trace the memory
+        unsafe { trace::increment_allocated(size) };
+        Some(Self { memory: ptr::slice_from_raw_parts_mut(memory, len) })
+    }
+    /// Creates a new uninitialized slice with `len` elements and an alignment of at least `align` bytes
+    ///
+    /// # Note
+    /// Operations that resize the memory via `realloc` (e.g. `Heap::try_resize_with`) may not preserve the alignment.
+    ///
+    /// # Panics
+    /// This function panics if `align` is not a power of two.
+    pub fn new_uninit_slice_aligned(len: usize, align: usize) -> Option<Self> {
+        // Allocate the memory
+        let (size, align) = (mem::size_of::<T>().checked_mul(len)?, align.max(mem::align_of::<T>()));
+        let memory = malloc(size, align) as *mut MaybeUninit<T>;
+        if memory.is_null() {
+            return None;
+        }
+
+        // Trace the memory
+        unsafe { trace::increment_allocated(size) };
+        Some(Self { memory: ptr::slice_from_raw_parts_mut(memory, len) })
+    }
+
+    /// Assumes that all elements of the slice have been initialized
+    ///
+    /// # Safety
+    /// See
+    /// [core::mem::MaybeUninit::assume_init](https://doc.rust-lang.org/stable/core/mem/union.MaybeUninit.html#method.assume_init)
+    /// for more information.
+    pub unsafe fn assume_init(self) -> Heap<[T]> {
+        // Consume `self` and create a new instance with the appropriate pointer type
+        let memory = self.into_raw();
+        Heap { memory: memory as *mut [T] }
+    }
+
+    /// Resizes the slice to `len` elements via `realloc`, which may move the memory to a new location
+    ///
+    /// # Note
+    /// If the reallocation fails, the slice is left unchanged.
+    pub(crate) fn try_realloc(&mut self, len: usize) -> Result<(), AllocError> {
+        // Reallocate the memory; never request zero bytes since `realloc` may release the memory in this case
+        let (old_size, size) =
+            (mem::size_of_val(self.inner()), mem::size_of::<T>().checked_mul(len).ok_or(AllocError)?);
+        let memory = self.memory as *mut c_void;
+        let memory = unsafe { realloc(memory, old_size, size.max(1), mem::align_of::<T>()) as *mut MaybeUninit<T> };
+        if memory.is_null() {
+            return Err(AllocError);
+        }
+
+        // Trace the memory
+        unsafe { trace::decrement_allocated(old_size) };
+        unsafe { trace::increment_allocated(size) };
+        self.memory = ptr::slice_from_raw_parts_mut(memory, len);
+        Ok(())
+    }
+}
 impl<T> Heap<T> {
     /// The amount of heap-allocated memory in bytes
     pub const SIZE: usize = mem::size_of::<T>();
@@ -67,16 +239,24 @@ impl<T> Heap<T> {
         this.write(value);
         Ok(unsafe { this.assume_init() })
     }
-    /// Creates a heap object from a raw pointer that has been created with `Heap::into_raw`
+    /// Moves `value` to the heap with an alignment of at least `align` bytes
     ///
-    /// # Safety
-    /// This function is unsafe because improper use may lead to memory problems. For example, a double-free may occur if
-    /// the function is called twice on the same raw pointer.
-    pub unsafe fn from_raw(memory: *mut T) -> Self {
-        assert!(!memory.is_null(), "unexpected null pointer");
-        Self { memory }
-    }
+    /// # Note
+    /// Operations that resize the memory via `realloc` (e.g. `Heap::map_in_place`) may not preserve the alignment.
+    ///
+    /// # Panics
+    /// This function panics if `align` is not a power of two.
+    pub fn new_aligned(value: T, align: usize) -> Result<Self, T> {
+        // Allocate the memory
+        let mut this = match Heap::new_uninit_aligned(align) {
+            Some(this) => this,
+            None => return Err(value),
+        };
 
+        // Initialize the memory
+        this.write(value);
+        Ok(unsafe { this.assume_init() })
+    }
     /// Returns the underlying element
     pub fn into_inner(self) -> T {
         // Take the element and free the allocated memory
@@ -91,6 +271,80 @@ impl<T> Heap<T> {
         mem::forget(self);
         element
     }
+    /// Replaces the underlying element with `value` and returns the old element
+    pub fn replace(&mut self, value: T) -> T {
+        mem::replace(self.inner_mut(), value)
+    }
+    /// Replaces the underlying element with its default value and returns the old element
+    pub fn take(&mut self) -> T
+    where
+        T: Default,
+    {
+        mem::take(self.inner_mut())
+    }
+    /// Maps the underlying element to a new element and reuses the memory for it
+    ///
+    /// # Note
+    /// If `U` is not larger than `T`, the memory is reused as-is to avoid fragmentation. Otherwise, it is grown via
+    /// `realloc`, which may move the memory to a new location; if this fails, `f` is not called and `self` is returned
+    /// as error.
+    ///
+    /// If the alignment of `U` exceeds the alignment of the existing memory, the memory cannot be reused. In this case,
+    /// a new memory segment with the appropriate alignment is allocated instead; if this fails, `f` is not called and
+    /// `self` is returned as error.
+    pub fn map_in_place<U, F>(self, f: F) -> Result<Heap<U>, Self>
+    where
+        F: FnOnce(T) -> U,
+    {
+        // Allocate a new memory segment if the existing memory is not sufficiently aligned; every allocation is aligned
+        // to at least `MALLOC_ALIGN` bytes
+        if mem::align_of::<U>() > mem::align_of::<T>().max(MALLOC_ALIGN) {
+            let Some(mut memory) = Heap::<MaybeUninit<U>>::new_uninit_aligned(mem::align_of::<U>()) else {
+                return Err(self);
+            };
+            memory.write(f(self.into_inner()));
+            return Ok(unsafe { memory.assume_init() });
+        }
+
+        // Grow the memory if necessary
+        let (old_size, size) = (mem::size_of::<T>(), mem::size_of::<U>());
+        let mut memory = self.memory as *mut c_void;
+        if size > old_size {
+            let align = mem::align_of::<T>().max(mem::align_of::<U>());
+            memory = unsafe { realloc(memory, old_size, size, align) };
+            if memory.is_null() {
+                return Err(self);
+            }
+        }
+        mem::forget(self);
+
+        // Map the element; the memory is released if `f` panics
+        let uninit: Heap<MaybeUninit<T>> = Heap { memory: memory as *mut MaybeUninit<T> };
+        let element = f(unsafe { uninit.assume_init_read() });
+        mem::forget(uninit);
+
+        // Trace the memory and initialize the element; a larger memory is kept as-is
+        unsafe { trace::decrement_allocated(old_size) };
+        unsafe { trace::increment_allocated(size) };
+        let memory = memory as *mut U;
+        unsafe { memory.write(element) };
+        Ok(Heap { memory })
+    }
+}
+impl<T> Heap<T>
+where
+    T: ?Sized,
+{
+    /// Creates a heap object from a raw pointer that has been created with `Heap::into_raw`
+    ///
+    /// # Safety
+    /// This function is unsafe because improper use may lead to memory problems. For example, a double-free may occur if
+    /// the function is called twice on the same raw pointer.
+    pub unsafe fn from_raw(memory: *mut T) -> Self {
+        assert!(!memory.is_null(), "unexpected null pointer");
+        Self { memory }
+    }
+
     /// Consumes `self` and returns the underlying raw pointer
     ///
     /// # Note
@@ -113,6 +367,50 @@ impl<T> Heap<T> {
         let reference = unsafe { self.memory.as_mut() };
         reference.expect("unexpected null pointer")
     }
+
+    /// Converts `self` into a heap object of an unsized type (e.g. a trait object) by casting the underlying pointer
+    ///
+    /// # Note
+    /// Usually you want to use the safe [`heap_dyn!`](crate::heap_dyn) macro instead.
+    ///
+    /// # Safety
+    /// `cast` must return a pointer to the same object, i.e. it must only perform an unsizing conversion like
+    /// `|ptr| ptr as *mut dyn Trait`. As a sanity check, this function panics if the returned pointer has a different
+    /// address or a different size than the original pointer.
+    pub unsafe fn unsize<U, F>(self, cast: F) -> Heap<U>
+    where
+        U: ?Sized,
+        F: FnOnce(*mut T) -> *mut U,
+    {
+        // Perform the conversion
+        let size = mem::size_of_val(self.inner());
+        let original = self.into_raw();
+        let memory = cast(original);
+
+        // Validate the pointer and release the original memory if the conversion is invalid
+        let is_valid = original as *mut u8 == memory as *mut u8 && mem::size_of_val(&*memory) == size;
+        if !is_valid {
+            drop(Heap::from_raw(original));
+            panic!("invalid unsizing conversion");
+        }
+        Heap::from_raw(memory)
+    }
+}
+impl<T> Heap<T>
+where
+    T: Clone,
+{
+    /// Creates a copy of the heap object for types that only implement `Clone`
+    ///
+    /// # Note
+    /// Unlike `TryClone`, `Clone` cannot report allocation failures of the value itself, so this only reports a failed
+    /// allocation of the copy. For types that implement both traits, this function shadows `TryClone::try_clone`.
+    pub fn try_clone(&self) -> Result<Self, AllocError> {
+        // Allocate the memory first, so that a failed allocation does not waste a clone
+        let mut clone = Heap::new_uninit().ok_or(AllocError)?;
+        clone.write(self.inner().clone());
+        Ok(unsafe { clone.assume_init() })
+    }
 }
 impl<const LEN: usize, T> Heap<[T; LEN]> {
     /// Allocates a new segment on the heap and initializes it with `T::default()`
@@ -142,44 +440,446 @@ impl<const LEN: usize, T> Heap<[T; LEN]> {
         Some(unsafe { this.assume_init() })
     }
 }
-impl<T> Deref for Heap<T> {
+impl<T> Heap<[T]> {
+    /// Creates a new heap slice with the values yielded by `iter`
+    ///
+    /// # Note
+    /// The memory is allocated according to the lower bound of `Iterator::size_hint`, so that exactly sized iterators
+    /// need a single allocation. Additional elements grow the memory via `realloc`, and excess memory is released at
+    /// the end on a best-effort basis. If an allocation fails, the already collected elements are dropped and the memory
+    /// is released.
+    pub fn try_from_iter<I>(iter: I) -> Result<Self, AllocError>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        // Allocate the memory according to the size hint
+        let iter = iter.into_iter();
+        let (len, _) = iter.size_hint();
+        let memory = Heap::new_uninit_slice(len).ok_or(AllocError)?;
+
+        // Collect the elements and release the excess memory
+        let mut writer = SliceWriter::new(memory);
+        for value in iter {
+            writer.try_push_grow(value)?;
+        }
+        writer.shrink_to_fit();
+        Ok(writer.finish())
+    }
+    /// Resizes the slice to `len` elements, where new elements are created by `f`
+    ///
+    /// # Note
+    /// The memory is resized via `realloc`, which may move the memory to a new location. If growing the memory fails,
+    /// the slice is left unchanged; shrinking always succeeds like with `Heap::truncate`. If `f` panics, the slice keeps
+    /// the elements that have been created so far.
+    pub fn try_resize_with<F>(&mut self, len: usize, mut f: F) -> Result<(), AllocError>
+    where
+        F: FnMut() -> T,
+    {
+        // Shrink the slice if necessary
+        let old_len = self.len();
+        if len <= old_len {
+            self.truncate(len);
+            return Ok(());
+        }
+
+        // Grow the memory and create the new elements
+        self.as_uninit_mut().try_realloc(len)?;
+        let mut guard = ResizeGuard { memory: self.as_uninit_mut(), initialized: old_len };
+        while guard.initialized < len {
+            guard.memory[guard.initialized].write(f());
+            guard.initialized += 1;
+        }
+
+        // Forget the guard since all elements have been initialized
+        mem::forget(guard);
+        Ok(())
+    }
+    /// Shortens the slice to `len` elements and drops the excess elements; this has no effect if `len` is greater than
+    /// or equal to the current length
+    ///
+    /// # Note
+    /// The excess memory is released via `realloc` on a best-effort basis. If this fails, the slice is truncated
+    /// nonetheless and keeps the original memory.
+    pub fn truncate(&mut self, len: usize) {
+        // Ensure that the slice is longer than `len`
+        let old_len = self.len();
+        if len >= old_len {
+            return;
+        }
+
+        // Truncate the slice first so that the excess elements are not dropped twice if a drop panics
+        let memory = self.memory as *mut T;
+        self.memory = ptr::slice_from_raw_parts_mut(memory, len);
+        unsafe { trace::decrement_allocated(mem::size_of::<T>() * (old_len - len)) };
+        unsafe { ptr::slice_from_raw_parts_mut(memory.add(len), old_len - len).drop_in_place() };
+
+        // Release the excess memory; never request zero bytes since `realloc` may release the memory in this case
+        let size = mem::size_of::<T>() * len;
+        let old_size = mem::size_of::<T>() * old_len;
+        let shrunk = unsafe { realloc(memory as *mut c_void, old_size, size.max(1), mem::align_of::<T>()) as *mut T };
+        if !shrunk.is_null() {
+            self.memory = ptr::slice_from_raw_parts_mut(shrunk, len);
+        }
+    }
+
+    /// Reinterprets the slice as uninitialized slice
+    fn as_uninit_mut(&mut self) -> &mut Heap<[MaybeUninit<T>]> {
+        // `Heap` is a transparent wrapper around the pointer, and `MaybeUninit<T>` has the same layout as `T`
+        unsafe { &mut *(self as *mut Self as *mut Heap<[MaybeUninit<T>]>) }
+    }
+}
+impl<T> Heap<[T]>
+where
+    T: Clone,
+{
+    /// Creates a copy of the heap slice for element types that only implement `Clone`
+    ///
+    /// # Note
+    /// The elements are cloned one by one into the new memory. For types that implement both `Clone` and `TryClone`,
+    /// this function shadows `TryClone::try_clone`.
+    pub fn try_clone(&self) -> Result<Self, AllocError> {
+        let memory = Heap::new_uninit_slice(self.len()).ok_or(AllocError)?;
+        let mut clone = SliceWriter::new(memory);
+        self.iter().for_each(|element| clone.push(element.clone()));
+        Ok(clone.finish())
+    }
+}
+impl<T> TryClone for Heap<T>
+where
+    T: TryClone,
+{
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        // Allocate the memory first, so that a failed allocation does not waste a clone
+        let mut clone = Heap::new_uninit().ok_or(AllocError)?;
+        self.inner().try_clone_to_uninit(clone.inner_mut())?;
+        Ok(unsafe { clone.assume_init() })
+    }
+}
+impl<T> TryClone for Heap<[T]>
+where
+    T: TryClone,
+{
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        // Allocate the memory
+        let memory = Heap::new_uninit_slice(self.len()).ok_or(AllocError)?;
+        let mut clone = SliceWriter::new(memory);
+
+        // Clone the elements one by one into the memory
+        for element in self.iter() {
+            element.try_clone_to_uninit(&mut clone.memory[clone.initialized])?;
+            clone.initialized += 1;
+        }
+        Ok(clone.finish())
+    }
+}
+impl TryClone for Heap<str> {
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        // The copied bytes are valid UTF-8 since they originate from a string
+        let bytes = Heap::<[u8]>::try_from_iter(self.inner().bytes())?;
+        Ok(Heap { memory: bytes.into_raw() as *mut str })
+    }
+}
+impl<const LEN: usize, T> From<Heap<[T; LEN]>> for Heap<[T]> {
+    /// Converts the array into a slice without copying
+    fn from(value: Heap<[T; LEN]>) -> Self {
+        let memory = value.into_raw();
+        Heap { memory: memory as *mut [T] }
+    }
+}
+impl<const LEN: usize, T> TryFrom<Heap<[T]>> for Heap<[T; LEN]> {
+    type Error = Heap<[T]>;
+
+    /// Converts the slice into an array without copying, or returns the slice if it does not have `LEN` elements
+    fn try_from(value: Heap<[T]>) -> Result<Self, Self::Error> {
+        // Ensure that the length matches
+        if value.len() != LEN {
+            return Err(value);
+        }
+
+        // Convert the pointer type
+        let memory = value.into_raw();
+        Ok(Heap { memory: memory as *mut [T; LEN] })
+    }
+}
+impl<T> From<Heap<T>> for Heap<[T; 1]> {
+    /// Converts the element into a single-element array without copying
+    fn from(value: Heap<T>) -> Self {
+        let memory = value.into_raw();
+        Heap { memory: memory as *mut [T; 1] }
+    }
+}
+impl TryFrom<Heap<[u8]>> for Heap<str> {
+    type Error = Heap<[u8]>;
+
+    /// Converts the bytes into a string without copying, or returns the bytes if they are not valid UTF-8
+    fn try_from(value: Heap<[u8]>) -> Result<Self, Self::Error> {
+        // Validate the bytes
+        if str::from_utf8(value.inner()).is_err() {
+            return Err(value);
+        }
+
+        // Convert the pointer type
+        let memory = value.into_raw();
+        Ok(Heap { memory: memory as *mut str })
+    }
+}
+impl From<Heap<str>> for Heap<[u8]> {
+    /// Converts the string into its bytes without copying
+    fn from(value: Heap<str>) -> Self {
+        let memory = value.into_raw();
+        Heap { memory: memory as *mut [u8] }
+    }
+}
+impl<T> Deref for Heap<T>
+where
+    T: ?Sized,
+{
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
         self.inner()
     }
 }
-impl<T> DerefMut for Heap<T> {
+impl<T> DerefMut for Heap<T>
+where
+    T: ?Sized,
+{
     fn deref_mut(&mut self) -> &mut Self::Target {
         self.inner_mut()
     }
 }
-impl<T> AsRef<T> for Heap<T> {
+impl<T> AsRef<T> for Heap<T>
+where
+    T: ?Sized,
+{
     fn as_ref(&self) -> &T {
         self.inner()
     }
 }
-impl<T> AsMut<T> for Heap<T> {
+impl<T> AsMut<T> for Heap<T>
+where
+    T: ?Sized,
+{
     fn as_mut(&mut self) -> &mut T {
         self.inner_mut()
     }
 }
+impl<T> Borrow<T> for Heap<T>
+where
+    T: ?Sized,
+{
+    fn borrow(&self) -> &T {
+        self.inner()
+    }
+}
+impl<T> BorrowMut<T> for Heap<T>
+where
+    T: ?Sized,
+{
+    fn borrow_mut(&mut self) -> &mut T {
+        self.inner_mut()
+    }
+}
+impl<T> PartialEq for Heap<T>
+where
+    T: PartialEq + ?Sized,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.inner().eq(other.inner())
+    }
+}
+impl<T> Eq for Heap<T> where T: Eq + ?Sized {}
+impl<T> PartialOrd for Heap<T>
+where
+    T: PartialOrd + ?Sized,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.inner().partial_cmp(other.inner())
+    }
+}
+impl<T> Ord for Heap<T>
+where
+    T: Ord + ?Sized,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.inner().cmp(other.inner())
+    }
+}
+impl<T> Hash for Heap<T>
+where
+    T: Hash + ?Sized,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner().hash(state)
+    }
+}
 impl<T> Debug for Heap<T>
 where
-    T: Debug,
+    T: Debug + ?Sized,
 {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         self.as_ref().fmt(f)
     }
 }
-impl<T> Drop for Heap<T> {
+impl<T> Display for Heap<T>
+where
+    T: Display + ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.inner().fmt(f)
+    }
+}
+impl<T> Pointer for Heap<T>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Pointer::fmt(&self.memory, f)
+    }
+}
+impl<T> Error for Heap<T>
+where
+    T: Error + ?Sized,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.inner().source()
+    }
+}
+impl<I> Iterator for Heap<I>
+where
+    I: Iterator + ?Sized,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner_mut().next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner().size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner_mut().nth(n)
+    }
+}
+impl<I> DoubleEndedIterator for Heap<I>
+where
+    I: DoubleEndedIterator + ?Sized,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner_mut().next_back()
+    }
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner_mut().nth_back(n)
+    }
+}
+impl<I> ExactSizeIterator for Heap<I>
+where
+    I: ExactSizeIterator + ?Sized,
+{
+    fn len(&self) -> usize {
+        self.inner().len()
+    }
+}
+impl<I> FusedIterator for Heap<I> where I: FusedIterator + ?Sized {}
+impl<T> Drop for Heap<T>
+where
+    T: ?Sized,
+{
     fn drop(&mut self) {
+        // Get the size before the element is dropped
+        let size = mem::size_of_val(self.inner());
+
         // Drop the element and release the memory
         unsafe { self.memory.drop_in_place() };
         unsafe { free(self.memory as *mut c_void) }
 
         // Trace the memory
-        let size = mem::size_of::<T>();
         unsafe { trace::decrement_allocated(size) };
     }
 }
+
+/// A writer to initialize a heap-allocated slice that drops the already initialized elements if it is dropped before
+/// completion (e.g. due to an early return or a panic)
+struct SliceWriter<T> {
+    /// The underlying memory
+    memory: Heap<[MaybeUninit<T>]>,
+    /// The amount of initialized elements
+    initialized: usize,
+}
+impl<T> SliceWriter<T> {
+    /// Creates a new writer for the given uninitialized slice
+    pub const fn new(memory: Heap<[MaybeUninit<T>]>) -> Self {
+        Self { memory, initialized: 0 }
+    }
+
+    /// Initializes the next element
+    pub fn push(&mut self, value: T) {
+        self.memory[self.initialized].write(value);
+        self.initialized += 1;
+    }
+    /// Initializes the next element and grows the slice if necessary
+    pub fn try_push_grow(&mut self, value: T) -> Result<(), AllocError> {
+        // Double the capacity if the slice is full
+        if self.initialized == self.memory.len() {
+            let len = self.memory.len().checked_mul(2).ok_or(AllocError)?.max(4);
+            self.memory.try_realloc(len)?;
+        }
+
+        // Initialize the element
+        self.push(value);
+        Ok(())
+    }
+    /// Shrinks the slice to the initialized elements; if releasing the excess memory fails, the memory is kept
+    pub fn shrink_to_fit(&mut self) {
+        self.memory.truncate(self.initialized);
+    }
+
+    /// Returns the initialized slice
+    pub fn finish(self) -> Heap<[T]> {
+        assert_eq!(self.initialized, self.memory.len(), "slice is not fully initialized");
+
+        // Take the memory and forget `self` to avoid that the elements are dropped
+        let memory = unsafe { ptr::read(&self.memory) };
+        mem::forget(self);
+        unsafe { memory.assume_init() }
+    }
+}
+impl<T> Drop for SliceWriter<T> {
+    fn drop(&mut self) {
+        // Drop the initialized elements; the memory itself is released by the `Heap`
+        let initialized = &mut self.memory[..self.initialized] as *mut [MaybeUninit<T>] as *mut [T];
+        unsafe { initialized.drop_in_place() };
+    }
+}
+
+/// A guard that truncates a partially grown slice to the initialized elements if it is dropped before completion (e.g.
+/// due to a panic)
+struct ResizeGuard<'a, T> {
+    /// The underlying memory
+    memory: &'a mut Heap<[MaybeUninit<T>]>,
+    /// The amount of initialized elements
+    initialized: usize,
+}
+impl<T> Drop for ResizeGuard<'_, T> {
+    fn drop(&mut self) {
+        // Uninitialized elements have no drop glue, so this only releases the excess memory
+        self.memory.truncate(self.initialized);
+    }
+}
+
+/// Moves a value to the heap and converts it into a heap object of an unsized type like `dyn Trait`
+///
+/// This is a safe wrapper around [`Heap::new`] and [`Heap::unsize`] that evaluates to `Result<Heap<$target>, T>`:
+/// ```
+/// # use core::fmt::Debug;
+/// # use picosdk_malloc::{heap_dyn, Heap};
+/// let heap: Heap<dyn Debug> = heap_dyn!(*b"Testolope" => dyn Debug).expect("failed to allocate memory");
+/// ```
+#[macro_export]
+macro_rules! heap_dyn {
+    ($value:expr => $target:ty) => {
+        $crate::heap::Heap::new($value)
+            // The implicit coercion ensures that only unsizing conversions are possible
+            .map(|heap| unsafe { heap.unsize(|ptr| -> *mut $target { ptr }) })
+    };
+}
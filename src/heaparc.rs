@@ -0,0 +1,693 @@
+//! A `malloc`/`free`-managed reference-counted heap object that can be shared between cores
+//!
+//! # Locking
+//! Since the RP2040 does not support atomic read-modify-write operations, all reference counter updates are performed
+//! within the critical section of a pluggable [`RawLock`]. Values are never dropped and memory is never allocated or
+//! released within the critical section.
+
+pub use crate::refcounter::{RefCount, OVERHEAD};
+
+use crate::{
+    error::{AllocError, RefCountOverflow},
+    heap::Heap,
+    lock::RawLock,
+    refcounter::RefCounter,
+    tryclone::TryClone,
+};
+use core::{
+    borrow::Borrow,
+    cmp::Ordering,
+    error::Error,
+    ffi::c_void,
+    fmt::{self, Debug, Display, Formatter, Pointer},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    ptr::{self, NonNull},
+};
+
+/// A reference counted heap object that can be shared between cores
+pub struct HeapArc<T, L>
+where
+    L: RawLock,
+{
+    /// The referenced value
+    value: *mut T,
+    /// The reference counter
+    refctr: *mut RefCounter,
+    /// The lock to protect the reference counter
+    lock: PhantomData<L>,
+}
+impl<T, L> HeapArc<T, L>
+where
+    L: RawLock,
+{
+    /// The amount of heap-allocated memory in bytes
+    pub const SIZE: usize = RefCounter::size::<T>();
+
+    /// Creates a new reference counted heap object from the given heap object
+    ///
+    /// # Note
+    /// Since the value shares the allocation with the reference counters, the memory of `value` is grown via `realloc`
+    /// and the value is moved behind the reference counters. This only fails if `realloc` fails, in which case `value`
+    /// is returned as error.
+    pub fn new_from_heap(value: Heap<T>) -> Result<Self, Heap<T>> {
+        // Grow the memory with a reference counter that resembles one strong reference
+        let memory = value.into_raw() as *mut c_void;
+        match unsafe { RefCounter::from_heap(memory, mem::size_of::<T>(), mem::align_of::<T>(), 1, 1) } {
+            Some(refctr) => Ok(unsafe { Self::from_refctr(refctr) }),
+            None => Err(unsafe { Heap::from_raw(memory as *mut T) }),
+        }
+    }
+    /// Creates a new reference counted heap object with the given value
+    pub fn new(value: T) -> Result<Self, T> {
+        // Allocate the memory with a reference counter that resembles one strong reference
+        let Some(refctr) = RefCounter::alloc::<T>(1, 1) else {
+            return Err(value);
+        };
+
+        // Initialize the value
+        let this = unsafe { Self::from_refctr(refctr) };
+        unsafe { this.value.write(value) };
+        Ok(this)
+    }
+    /// Creates a new reference counted heap object with the value returned by `generator`, which gets a weak reference
+    /// to the object under construction
+    ///
+    /// # Note
+    /// Until the construction completes, the weak reference cannot be upgraded. If the allocation fails or `generator`
+    /// panics, all allocated memory is released.
+    pub fn try_new_cyclic<F>(generator: F) -> Result<Self, AllocError>
+    where
+        F: FnOnce(&HeapArcWeak<T, L>) -> T,
+    {
+        // Allocate the memory with a reference counter that resembles one weak reference
+        let refctr = RefCounter::alloc::<T>(0, 1).ok_or(AllocError)?;
+        let value = unsafe { RefCounter::value(refctr) };
+
+        // Create the weak reference and generate the value
+        let weak = HeapArcWeak { value, refctr, lock: PhantomData };
+        let generated = generator(&weak);
+        unsafe { value.write(generated) };
+
+        // Register the strong reference, which takes over the temporary weak reference as implicit weak reference
+        L::critical_section(|| unsafe { (*refctr).strong = 1 });
+        mem::forget(weak);
+        Ok(Self { value, refctr, lock: PhantomData })
+    }
+
+    /// Recreates a reference counted heap object from a raw pointer that has been created with `HeapArc::into_raw`
+    ///
+    /// # Safety
+    /// This function is unsafe because improper use may lead to memory problems. For example, a double-free may occur if
+    /// the function is called twice on the same raw pointer.
+    pub unsafe fn from_raw(value: *const T) -> Self {
+        assert!(!value.is_null(), "unexpected null pointer");
+        let value = value as *mut T;
+        Self { value, refctr: RefCounter::from_value(value), lock: PhantomData }
+    }
+    /// Consumes `self` and returns a raw pointer to the underlying value
+    ///
+    /// # Note
+    /// The strong reference is not released. To release it, recreate a `HeapArc` from the raw pointer with
+    /// `HeapArc::from_raw` and drop it accordingly, or use `HeapArc::decrement_strong_count`.
+    pub fn into_raw(self) -> *const T {
+        let value = self.value;
+        mem::forget(self);
+        value
+    }
+    /// Increments the strong reference count of the object that `value` points to
+    ///
+    /// # Panics
+    /// This function panics if the strong reference count would overflow.
+    ///
+    /// # Safety
+    /// `value` must have been obtained via `HeapArc::into_raw` and the associated strong reference must still be alive.
+    pub unsafe fn increment_strong_count(value: *const T) {
+        let refctr = RefCounter::from_value(value as *mut T);
+        let result = L::critical_section(|| RefCounter::increment_strong(refctr));
+        result.expect("reference count overflow");
+    }
+    /// Decrements the strong reference count of the object that `value` points to and releases it if necessary
+    ///
+    /// # Safety
+    /// `value` must have been obtained via `HeapArc::into_raw` and the associated strong reference must still be alive.
+    /// The associated strong reference is released by this call.
+    pub unsafe fn decrement_strong_count(value: *const T) {
+        drop(Self::from_raw(value));
+    }
+
+    /// A raw pointer to the underlying value
+    pub fn as_ptr(&self) -> *const T {
+        self.value
+    }
+    /// Whether `self` and `other` reference the same object
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        ptr::eq(self.value, other.value)
+    }
+    /// A reference to the underlying value
+    pub fn inner(&self) -> &T {
+        let reference = unsafe { self.value.as_ref() };
+        reference.expect("unexpected null pointer")
+    }
+    /// A mutable reference to the underlying value if `self` is the only reference (strong or weak) to it
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // Ensure that we are the only reference
+        if !self.is_unique() {
+            return None;
+        }
+
+        // Create the reference
+        let reference = unsafe { self.value.as_mut() };
+        Some(reference.expect("unexpected null pointer"))
+    }
+    /// A mutable reference to the underlying value, which is cloned first if there are other strong references to it
+    ///
+    /// # Note
+    /// Like `Arc::make_mut`, this function disassociates all weak references if `self` is the last strong reference;
+    /// i.e. they cannot be upgraded anymore.
+    pub fn try_make_mut(&mut self) -> Result<&mut T, AllocError>
+    where
+        T: Clone,
+    {
+        if !self.is_unique() {
+            // Allocate the memory with a temporary weak reference that releases the memory on error
+            let refctr = RefCounter::alloc::<T>(0, 1).ok_or(AllocError)?;
+            let weak =
+                HeapArcWeak::<T, L> { value: unsafe { RefCounter::value::<T>(refctr) }, refctr, lock: PhantomData };
+
+            // Take the value if we are the last strong reference, so that the weak references cannot upgrade anymore
+            let old_refctr = self.refctr;
+            let is_last = L::critical_section(|| unsafe {
+                let is_last = (*old_refctr).strong == 1;
+                if is_last {
+                    (*old_refctr).strong = 0;
+                }
+                is_last
+            });
+
+            // Move or clone the value
+            match is_last {
+                true => unsafe { ptr::copy_nonoverlapping(self.value, weak.value, 1) },
+                false => unsafe { weak.value.write(self.inner().clone()) },
+            }
+
+            // Register the strong reference, which takes over the temporary weak reference
+            unsafe { (*refctr).strong = 1 };
+            let this = Self { value: weak.value, refctr, lock: PhantomData };
+            mem::forget(weak);
+
+            // Replace `self` and release the old reference
+            let old = mem::replace(self, this);
+            match is_last {
+                true => unsafe { old.release_implicit_weak() },
+                false => drop(old),
+            }
+        }
+
+        // We are the only reference now
+        let reference = self.get_mut();
+        Ok(reference.expect("unexpected shared reference"))
+    }
+
+    /// The amount of strong references to the underlying value
+    pub fn strong(&self) -> usize {
+        L::critical_section(|| unsafe { RefCounter::strong(self.refctr) })
+    }
+    /// The amount of weak references to the underlying value
+    pub fn weak(&self) -> usize {
+        L::critical_section(|| unsafe { RefCounter::weak(self.refctr) })
+    }
+
+    /// Creates a new strong reference to the heap allocated object or returns an error if the strong reference count
+    /// would overflow
+    pub fn try_clone_ref(&self) -> Result<Self, RefCountOverflow> {
+        L::critical_section(|| unsafe { RefCounter::increment_strong(self.refctr) })?;
+        Ok(Self { value: self.value, refctr: self.refctr, lock: PhantomData })
+    }
+    /// Creates a weak reference to the heap allocated object
+    ///
+    /// # Panics
+    /// This function panics if the weak reference count would overflow.
+    pub fn downgrade(&self) -> HeapArcWeak<T, L> {
+        self.try_downgrade().expect("reference count overflow")
+    }
+    /// Creates a weak reference to the heap allocated object or returns an error if the weak reference count would
+    /// overflow
+    pub fn try_downgrade(&self) -> Result<HeapArcWeak<T, L>, RefCountOverflow> {
+        L::critical_section(|| unsafe { RefCounter::increment_weak(self.refctr) })?;
+        Ok(HeapArcWeak { value: self.value, refctr: self.refctr, lock: PhantomData })
+    }
+
+    /// Returns the underlying element as heap-object
+    ///
+    /// # Note
+    /// If `self` is the only reference, the value is moved to the start of the memory, which is then shrunk via
+    /// `realloc` on a best-effort basis. If there are weak references left, they keep the memory alive, so the value is
+    /// copied into a newly allocated heap object instead. If this allocation fails, `self` is returned as error.
+    pub fn try_unwrap_heap(self) -> Result<Heap<T>, Self> {
+        // Reuse the memory if `self` is the only reference, which cannot change since there are no other references
+        if self.is_unique() {
+            let memory = unsafe { RefCounter::into_heap(self.refctr, mem::size_of::<T>(), mem::align_of::<T>()) };
+            mem::forget(self);
+            return Ok(unsafe { Heap::from_raw(memory as *mut T) });
+        }
+
+        // Allocate the heap object
+        let Some(mut value) = Heap::new_uninit() else {
+            return Err(self);
+        };
+
+        // Ensure that we are the last strong reference and move the value
+        if !self.take_last() {
+            return Err(self);
+        }
+        unsafe { ptr::copy_nonoverlapping(self.value, value.as_mut_ptr(), 1) };
+
+        // Release the implicit weak reference without dropping the value
+        unsafe { self.release_implicit_weak() };
+        Ok(unsafe { value.assume_init() })
+    }
+    /// Returns the underlying element if `self` is the last strong reference, or releases `self` otherwise
+    pub fn into_inner(self) -> Option<T> {
+        self.try_unwrap().ok()
+    }
+    /// Returns the underlying element if `self` is the last strong reference, or a clone of it otherwise
+    pub fn unwrap_or_clone(self) -> T
+    where
+        T: Clone,
+    {
+        match self.try_unwrap() {
+            Ok(value) => value,
+            Err(this) => this.inner().clone(),
+        }
+    }
+    /// Returns the underlying element
+    pub fn try_unwrap(self) -> Result<T, Self> {
+        // Ensure that we are the last strong reference
+        if !self.take_last() {
+            return Err(self);
+        }
+
+        // Take the value and release the implicit weak reference without dropping the value
+        let value = unsafe { self.value.read() };
+        unsafe { self.release_implicit_weak() };
+        Ok(value)
+    }
+
+    /// Creates a new instance from a reference counter that has been allocated via `RefCounter::alloc`
+    ///
+    /// # Safety
+    /// This function is unsafe because the reference counter is not modified.
+    unsafe fn from_refctr(refctr: *mut RefCounter) -> Self {
+        Self { value: RefCounter::value(refctr), refctr, lock: PhantomData }
+    }
+    /// Whether `self` is the only reference (strong or weak) to the underlying value
+    fn is_unique(&self) -> bool {
+        L::critical_section(|| unsafe { RefCounter::strong(self.refctr) == 1 && RefCounter::weak(self.refctr) == 0 })
+    }
+    /// Sets the strong reference count to zero if `self` is the last strong reference, so that the value can be taken
+    fn take_last(&self) -> bool {
+        L::critical_section(|| unsafe {
+            let is_last = (*self.refctr).strong == 1;
+            if is_last {
+                (*self.refctr).strong = 0;
+            }
+            is_last
+        })
+    }
+    /// Releases the implicit weak reference after the value has been moved out of the allocation
+    ///
+    /// # Safety
+    /// This function is unsafe because the value is not dropped; i.e. the strong reference count must be zero and the
+    /// value must have been moved before.
+    unsafe fn release_implicit_weak(self) {
+        // Release the implicit weak reference and the memory if there are no weak references left
+        let is_last = L::critical_section(|| RefCounter::decrement_weak(self.refctr));
+        if is_last {
+            RefCounter::free(self.refctr, self.value);
+        }
+
+        // Forget `self` to avoid double-free during `drop()`
+        mem::forget(self);
+    }
+}
+impl<T, L> Deref for HeapArc<T, L>
+where
+    L: RawLock,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner()
+    }
+}
+impl<T, L> AsRef<T> for HeapArc<T, L>
+where
+    L: RawLock,
+{
+    fn as_ref(&self) -> &T {
+        self.inner()
+    }
+}
+impl<T, L> Borrow<T> for HeapArc<T, L>
+where
+    L: RawLock,
+{
+    fn borrow(&self) -> &T {
+        self.inner()
+    }
+}
+impl<T, L> PartialEq for HeapArc<T, L>
+where
+    T: PartialEq,
+    L: RawLock,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.inner().eq(other.inner())
+    }
+}
+impl<T, L> Eq for HeapArc<T, L>
+where
+    T: Eq,
+    L: RawLock,
+{
+}
+impl<T, L> PartialOrd for HeapArc<T, L>
+where
+    T: PartialOrd,
+    L: RawLock,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.inner().partial_cmp(other.inner())
+    }
+}
+impl<T, L> Ord for HeapArc<T, L>
+where
+    T: Ord,
+    L: RawLock,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.inner().cmp(other.inner())
+    }
+}
+impl<T, L> Hash for HeapArc<T, L>
+where
+    T: Hash,
+    L: RawLock,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner().hash(state)
+    }
+}
+impl<T, L> Debug for HeapArc<T, L>
+where
+    T: Debug,
+    L: RawLock,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.inner().fmt(f)
+    }
+}
+impl<T, L> Display for HeapArc<T, L>
+where
+    T: Display,
+    L: RawLock,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.inner().fmt(f)
+    }
+}
+impl<T, L> Pointer for HeapArc<T, L>
+where
+    L: RawLock,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Pointer::fmt(&self.value, f)
+    }
+}
+impl<T, L> Error for HeapArc<T, L>
+where
+    T: Error,
+    L: RawLock,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.inner().source()
+    }
+}
+impl<T, L> Clone for HeapArc<T, L>
+where
+    L: RawLock,
+{
+    /// Creates a new strong reference to the heap allocated object
+    ///
+    /// # Panics
+    /// This function panics if the strong reference count would overflow.
+    fn clone(&self) -> Self {
+        self.try_clone_ref().expect("reference count overflow")
+    }
+}
+impl<T, L> TryClone for HeapArc<T, L>
+where
+    L: RawLock,
+{
+    /// Creates a new strong reference to the heap allocated object
+    ///
+    /// # Errors
+    /// A strong reference count overflow is reported as `AllocError`.
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        self.try_clone_ref().map_err(|_| AllocError)
+    }
+}
+impl<T, L> Drop for HeapArc<T, L>
+where
+    L: RawLock,
+{
+    fn drop(&mut self) {
+        // Decrement the reference counter and drop the value if we are the last strong reference
+        let is_last = L::critical_section(|| unsafe { RefCounter::decrement_strong(self.refctr) });
+        if !is_last {
+            return;
+        }
+        unsafe { self.value.drop_in_place() };
+
+        // Release the implicit weak reference and the memory if there are no weak references left
+        let is_last = L::critical_section(|| unsafe { RefCounter::decrement_weak(self.refctr) });
+        if is_last {
+            unsafe { RefCounter::free(self.refctr, self.value) };
+        }
+    }
+}
+unsafe impl<T, L> Send for HeapArc<T, L>
+where
+    T: Send + Sync,
+    L: RawLock,
+{
+}
+unsafe impl<T, L> Sync for HeapArc<T, L>
+where
+    T: Send + Sync,
+    L: RawLock,
+{
+}
+
+/// A weak reference to a reference counted heap object that can be shared between cores
+pub struct HeapArcWeak<T, L>
+where
+    L: RawLock,
+{
+    /// The referenced value
+    value: *mut T,
+    /// The reference counter
+    refctr: *mut RefCounter,
+    /// The lock to protect the reference counter
+    lock: PhantomData<L>,
+}
+impl<T, L> HeapArcWeak<T, L>
+where
+    L: RawLock,
+{
+    /// Creates a new dangling weak reference that cannot be upgraded
+    ///
+    /// # Note
+    /// This function does not allocate any memory.
+    pub const fn new() -> Self {
+        Self { value: NonNull::dangling().as_ptr(), refctr: ptr::null_mut(), lock: PhantomData }
+    }
+
+    /// Recreates a weak reference from a raw pointer that has been created with `HeapArcWeak::into_raw`
+    ///
+    /// # Safety
+    /// This function is unsafe because improper use may lead to memory problems. For example, a double-free may occur if
+    /// the function is called twice on the same raw pointer.
+    pub unsafe fn from_raw(value: *const T) -> Self {
+        // Recreate dangling references as-is
+        let value = value as *mut T;
+        if value == NonNull::dangling().as_ptr() {
+            return Self::new();
+        }
+
+        // Recreate the reference
+        assert!(!value.is_null(), "unexpected null pointer");
+        Self { value, refctr: RefCounter::from_sized_value(value), lock: PhantomData }
+    }
+    /// Consumes `self` and returns a raw pointer to the underlying value
+    ///
+    /// # Note
+    /// The weak reference is not released. To release it, recreate a `HeapArcWeak` from the raw pointer with
+    /// `HeapArcWeak::from_raw` and drop it accordingly, or use `HeapArcWeak::decrement_weak_count`. Since the value may
+    /// have been dropped already, the pointer must not be dereferenced unless there is a strong reference left.
+    pub fn into_raw(self) -> *const T {
+        let value = self.value;
+        mem::forget(self);
+        value
+    }
+    /// Increments the weak reference count of the object that `value` points to
+    ///
+    /// # Panics
+    /// This function panics if the weak reference count would overflow.
+    ///
+    /// # Safety
+    /// `value` must have been obtained via `HeapArcWeak::into_raw` and the associated weak reference must still be
+    /// alive.
+    pub unsafe fn increment_weak_count(value: *const T) {
+        let this = mem::ManuallyDrop::new(Self::from_raw(value));
+        let clone = (*this).clone();
+        mem::forget(clone);
+    }
+    /// Decrements the weak reference count of the object that `value` points to and releases it if necessary
+    ///
+    /// # Safety
+    /// `value` must have been obtained via `HeapArcWeak::into_raw` and the associated weak reference must still be
+    /// alive. The associated weak reference is released by this call.
+    pub unsafe fn decrement_weak_count(value: *const T) {
+        drop(Self::from_raw(value));
+    }
+
+    /// A raw pointer to the underlying value
+    ///
+    /// # Note
+    /// Since the value may have been dropped already, the pointer must not be dereferenced unless there is a strong
+    /// reference left.
+    pub fn as_ptr(&self) -> *const T {
+        self.value
+    }
+    /// Whether `self` and `other` reference the same object, or whether both are dangling
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        ptr::eq(self.value, other.value)
+    }
+
+    /// The amount of strong references to the underlying value
+    pub fn strong(&self) -> usize {
+        if self.is_dangling() {
+            return 0;
+        }
+        L::critical_section(|| unsafe { RefCounter::strong(self.refctr) })
+    }
+    /// The amount of weak references to the underlying value
+    pub fn weak(&self) -> usize {
+        if self.is_dangling() {
+            return 0;
+        }
+        L::critical_section(|| unsafe { RefCounter::weak(self.refctr) })
+    }
+
+    /// Creates a new weak reference to the heap allocated object or returns an error if the weak reference count would
+    /// overflow
+    pub fn try_clone_ref(&self) -> Result<Self, RefCountOverflow> {
+        // Increment the reference counter if any
+        if !self.is_dangling() {
+            L::critical_section(|| unsafe { RefCounter::increment_weak(self.refctr) })?;
+        }
+        Ok(Self { value: self.value, refctr: self.refctr, lock: PhantomData })
+    }
+    /// Tries to create a strong reference to the heap object
+    ///
+    /// # Panics
+    /// This function panics if the strong reference count would overflow.
+    pub fn upgrade(&self) -> Option<HeapArc<T, L>> {
+        // Dangling references have no reference counter
+        if self.is_dangling() {
+            return None;
+        }
+
+        // Ensure that there is at least one strong reference left and update the reference counter
+        let result = L::critical_section(|| unsafe {
+            match (*self.refctr).strong {
+                0 => Ok(false),
+                _ => RefCounter::increment_strong(self.refctr).map(|_| true),
+            }
+        });
+        match result.expect("reference count overflow") {
+            true => Some(HeapArc { value: self.value, refctr: self.refctr, lock: PhantomData }),
+            false => None,
+        }
+    }
+
+    /// Whether `self` has been created by `HeapArcWeak::new` and thus has no reference counter
+    fn is_dangling(&self) -> bool {
+        self.refctr.is_null()
+    }
+}
+impl<T, L> Default for HeapArcWeak<T, L>
+where
+    L: RawLock,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T, L> Clone for HeapArcWeak<T, L>
+where
+    L: RawLock,
+{
+    /// Creates a new weak reference to the heap allocated object
+    ///
+    /// # Panics
+    /// This function panics if the weak reference count would overflow.
+    fn clone(&self) -> Self {
+        self.try_clone_ref().expect("reference count overflow")
+    }
+}
+impl<T, L> TryClone for HeapArcWeak<T, L>
+where
+    L: RawLock,
+{
+    /// Creates a new weak reference to the heap allocated object
+    ///
+    /// # Errors
+    /// A weak reference count overflow is reported as `AllocError`.
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        self.try_clone_ref().map_err(|_| AllocError)
+    }
+}
+impl<T, L> Drop for HeapArcWeak<T, L>
+where
+    L: RawLock,
+{
+    fn drop(&mut self) {
+        // Dangling references have no reference counter
+        if self.is_dangling() {
+            return;
+        }
+
+        // Decrement the reference counter and deallocate the memory if we are the last reference
+        let is_last = L::critical_section(|| unsafe { RefCounter::decrement_weak(self.refctr) });
+        if is_last {
+            unsafe { RefCounter::free(self.refctr, self.value) };
+        }
+    }
+}
+unsafe impl<T, L> Send for HeapArcWeak<T, L>
+where
+    T: Send + Sync,
+    L: RawLock,
+{
+}
+unsafe impl<T, L> Sync for HeapArcWeak<T, L>
+where
+    T: Send + Sync,
+    L: RawLock,
+{
+}
@@ -0,0 +1,158 @@
+//! A bump arena that carves many small objects from few `malloc`-allocated blocks
+
+use crate::{error::AllocError, heap, trace};
+use core::{
+    cell::Cell,
+    ffi::c_void,
+    mem,
+    ptr::{self, NonNull},
+    slice, str,
+};
+
+/// The header of a memory chunk, which is followed by the chunk's capacity
+struct ChunkHeader {
+    /// The previously allocated chunk if any
+    next: *mut ChunkHeader,
+    /// The capacity of the chunk in bytes, excluding the header
+    capacity: usize,
+}
+impl ChunkHeader {
+    /// Allocates a new chunk with the given capacity
+    fn alloc(capacity: usize, next: *mut Self) -> Option<NonNull<Self>> {
+        // Allocate the memory
+        let size = mem::size_of::<Self>().checked_add(capacity)?;
+        let memory = NonNull::new(heap::malloc(size, mem::align_of::<Self>()) as *mut Self)?;
+
+        // Initialize the header and trace the memory
+        unsafe { memory.as_ptr().write(Self { next, capacity }) };
+        unsafe { trace::increment_allocated(size) };
+        Some(memory)
+    }
+    /// Releases the chunk and returns the previously allocated chunk
+    ///
+    /// # Safety
+    /// This function is unsafe because improper use may lead to memory problems. For example, a double-free may occur if
+    /// the function is called twice on the same chunk.
+    unsafe fn free(this: NonNull<Self>) -> *mut Self {
+        let Self { next, capacity } = this.as_ptr().read();
+        heap::free(this.as_ptr() as *mut c_void);
+        trace::decrement_allocated(mem::size_of::<Self>() + capacity);
+        next
+    }
+    /// The start address of the chunk's capacity
+    fn data(this: NonNull<Self>) -> usize {
+        this.as_ptr() as usize + mem::size_of::<Self>()
+    }
+}
+
+/// A bump arena that carves objects from a single `malloc`-allocated block
+///
+/// # Note
+/// Objects that are allocated in the arena are never dropped; their memory is only reclaimed by `HeapArena::reset` or
+/// when the arena itself is dropped. If the arena is full, allocations fail unless the arena has been created with
+/// `HeapArena::new_growable`, which chains additional blocks instead.
+pub struct HeapArena {
+    /// The current chunk
+    chunk: Cell<NonNull<ChunkHeader>>,
+    /// The amount of used bytes within the current chunk
+    offset: Cell<usize>,
+    /// The capacity of additional chunks, or `None` if the arena cannot grow
+    growth: Option<usize>,
+}
+impl HeapArena {
+    /// Creates a new fixed-size arena with a capacity of `capacity` bytes
+    pub fn new(capacity: usize) -> Result<Self, AllocError> {
+        let chunk = ChunkHeader::alloc(capacity, ptr::null_mut()).ok_or(AllocError)?;
+        Ok(Self { chunk: Cell::new(chunk), offset: Cell::new(0), growth: None })
+    }
+    /// Creates a new arena with a capacity of `capacity` bytes that chains additional chunks with at least the same
+    /// capacity if it is full
+    pub fn new_growable(capacity: usize) -> Result<Self, AllocError> {
+        let chunk = ChunkHeader::alloc(capacity, ptr::null_mut()).ok_or(AllocError)?;
+        Ok(Self { chunk: Cell::new(chunk), offset: Cell::new(0), growth: Some(capacity) })
+    }
+
+    /// Moves `value` into the arena
+    #[allow(clippy::mut_from_ref, reason = "every allocation yields a distinct memory region")]
+    pub fn alloc<T>(&self, value: T) -> Option<&mut T> {
+        let memory = self.alloc_raw(mem::size_of::<T>(), mem::align_of::<T>())? as *mut T;
+        unsafe { memory.write(value) };
+        Some(unsafe { &mut *memory })
+    }
+    /// Copies `values` into the arena
+    #[allow(clippy::mut_from_ref, reason = "every allocation yields a distinct memory region")]
+    pub fn alloc_slice_copy<T>(&self, values: &[T]) -> Option<&mut [T]>
+    where
+        T: Copy,
+    {
+        let memory = self.alloc_raw(mem::size_of_val(values), mem::align_of::<T>())? as *mut T;
+        unsafe { ptr::copy_nonoverlapping(values.as_ptr(), memory, values.len()) };
+        Some(unsafe { slice::from_raw_parts_mut(memory, values.len()) })
+    }
+    /// Copies `value` into the arena
+    #[allow(clippy::mut_from_ref, reason = "every allocation yields a distinct memory region")]
+    pub fn alloc_str(&self, value: &str) -> Option<&mut str> {
+        let bytes = self.alloc_slice_copy(value.as_bytes())?;
+        Some(unsafe { str::from_utf8_unchecked_mut(bytes) })
+    }
+
+    /// Releases all objects and all chained chunks except for the first one
+    pub fn reset(&mut self) {
+        // Release all chunks except for the first one
+        let mut chunk = self.chunk.get();
+        while let Some(next) = NonNull::new(unsafe { chunk.as_ref() }.next) {
+            unsafe { ChunkHeader::free(chunk) };
+            chunk = next;
+        }
+
+        // Reset the arena
+        self.chunk.set(chunk);
+        self.offset.set(0);
+    }
+    /// The amount of free bytes in the current chunk
+    ///
+    /// # Note
+    /// Due to alignment padding, the amount of memory that is actually usable may be smaller.
+    pub fn remaining(&self) -> usize {
+        unsafe { self.chunk.get().as_ref() }.capacity - self.offset.get()
+    }
+
+    /// Allocates `size` bytes with the given alignment
+    fn alloc_raw(&self, size: usize, align: usize) -> Option<usize> {
+        // Try the current chunk
+        if let Some(memory) = self.bump(size, align) {
+            return Some(memory);
+        }
+
+        // Chain a new chunk that is large enough for the object including the alignment padding
+        let capacity = size.checked_add(align - 1)?.max(self.growth?);
+        let chunk = ChunkHeader::alloc(capacity, self.chunk.get().as_ptr())?;
+        self.chunk.set(chunk);
+        self.offset.set(0);
+        self.bump(size, align)
+    }
+    /// Allocates `size` bytes with the given alignment from the current chunk if possible
+    fn bump(&self, size: usize, align: usize) -> Option<usize> {
+        // Compute the aligned start and the end of the allocation
+        let chunk = self.chunk.get();
+        let data = ChunkHeader::data(chunk);
+        let start = (data + self.offset.get()).checked_next_multiple_of(align)?;
+        let end = start.checked_add(size)?;
+        if end > data + unsafe { chunk.as_ref() }.capacity {
+            return None;
+        }
+
+        // Update the offset
+        self.offset.set(end - data);
+        Some(start)
+    }
+}
+impl Drop for HeapArena {
+    fn drop(&mut self) {
+        // Release all chunks
+        let mut chunk = self.chunk.get().as_ptr();
+        while let Some(current) = NonNull::new(chunk) {
+            chunk = unsafe { ChunkHeader::free(current) };
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! A fixed-size object pool that recycles `malloc`-allocated slots without fragmentation
+
+use crate::{error::AllocError, heap, trace};
+use core::{
+    cell::Cell,
+    ffi::c_void,
+    fmt::{self, Debug, Display, Formatter},
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+};
+
+/// A pool slot that either holds a value or links to the next free slot
+union Slot<T> {
+    /// The next free slot if the slot is free
+    next: *mut Slot<T>,
+    /// The value if the slot is in use
+    value: ManuallyDrop<T>,
+}
+
+/// The header of a chunk of slots, which is followed by the slots
+struct ChunkHeader {
+    /// The previously allocated chunk if any
+    next: *mut ChunkHeader,
+    /// The amount of slots within the chunk
+    len: usize,
+}
+impl ChunkHeader {
+    /// The offset of the slots within a chunk
+    const fn offset<T>() -> usize {
+        mem::size_of::<Self>().next_multiple_of(mem::align_of::<Slot<T>>())
+    }
+    /// The amount of memory for a chunk with `len` slots
+    fn size<T>(len: usize) -> Option<usize> {
+        mem::size_of::<Slot<T>>().checked_mul(len)?.checked_add(Self::offset::<T>())
+    }
+
+    /// Allocates a new chunk with `len` slots and links the slots into a free list that ends with `free`
+    ///
+    /// # Note
+    /// The first slot is returned alongside the chunk.
+    fn alloc<T>(len: usize, next: *mut Self, free: *mut Slot<T>) -> Option<(NonNull<Self>, *mut Slot<T>)> {
+        // Allocate the memory
+        let size = Self::size::<T>(len)?;
+        let align = mem::align_of::<Self>().max(mem::align_of::<Slot<T>>());
+        let memory = NonNull::new(heap::malloc(size, align) as *mut Self)?;
+        unsafe { memory.as_ptr().write(Self { next, len }) };
+        unsafe { trace::increment_allocated(size) };
+
+        // Link the slots into the free list in reverse order, so that the first slot is handed out first
+        let slots = unsafe { (memory.as_ptr() as *mut u8).add(Self::offset::<T>()) as *mut Slot<T> };
+        let mut head = free;
+        for index in (0..len).rev() {
+            let slot = unsafe { slots.add(index) };
+            unsafe { slot.write(Slot { next: head }) };
+            head = slot;
+        }
+        Some((memory, head))
+    }
+    /// Releases the chunk and returns the previously allocated chunk
+    ///
+    /// # Safety
+    /// This function is unsafe because improper use may lead to memory problems. For example, a double-free may occur if
+    /// the function is called twice on the same chunk.
+    unsafe fn free<T>(this: NonNull<Self>) -> *mut Self {
+        let Self { next, len } = this.as_ptr().read();
+        heap::free(this.as_ptr() as *mut c_void);
+        trace::decrement_allocated(Self::size::<T>(len).expect("chunk size has been validated on allocation"));
+        next
+    }
+}
+
+/// A pool of equally-sized slots that are allocated up front and recycled in O(1)
+///
+/// # Note
+/// If all slots are in use, allocations fail unless the pool has been created with `HeapPool::new_growable`, which
+/// allocates additional chunks instead. Chunks are only released when the pool itself is dropped.
+pub struct HeapPool<T> {
+    /// The most recently allocated chunk
+    chunks: Cell<*mut ChunkHeader>,
+    /// The first free slot
+    free: Cell<*mut Slot<T>>,
+    /// The total amount of slots
+    capacity: Cell<usize>,
+    /// The amount of free slots
+    available: Cell<usize>,
+    /// The amount of slots in additional chunks, or `None` if the pool cannot grow
+    growth: Option<usize>,
+}
+impl<T> HeapPool<T> {
+    /// Creates a new fixed-size pool with `capacity` slots
+    pub fn new(capacity: usize) -> Result<Self, AllocError> {
+        Self::with_growth(capacity, None)
+    }
+    /// Creates a new pool with `capacity` slots that allocates additional chunks with the same amount of slots if all
+    /// slots are in use
+    pub fn new_growable(capacity: usize) -> Result<Self, AllocError> {
+        Self::with_growth(capacity, Some(capacity.max(1)))
+    }
+    /// Creates a new pool with `capacity` slots and the given growth
+    fn with_growth(capacity: usize, growth: Option<usize>) -> Result<Self, AllocError> {
+        let (chunk, free) = ChunkHeader::alloc::<T>(capacity, ptr::null_mut(), ptr::null_mut()).ok_or(AllocError)?;
+        Ok(Self {
+            chunks: Cell::new(chunk.as_ptr()),
+            free: Cell::new(free),
+            capacity: Cell::new(capacity),
+            available: Cell::new(capacity),
+            growth,
+        })
+    }
+
+    /// The total amount of slots
+    pub fn capacity(&self) -> usize {
+        self.capacity.get()
+    }
+    /// The amount of free slots
+    pub fn available(&self) -> usize {
+        self.available.get()
+    }
+
+    /// Moves `value` into a free slot or returns it as error if no slot is available
+    pub fn alloc(&self, value: T) -> Result<PoolBox<'_, T>, T> {
+        // Grow the pool if necessary
+        if self.free.get().is_null() {
+            let Some(growth) = self.growth else {
+                return Err(value);
+            };
+            let Some((chunk, free)) = ChunkHeader::alloc::<T>(growth, self.chunks.get(), ptr::null_mut()) else {
+                return Err(value);
+            };
+            self.chunks.set(chunk.as_ptr());
+            self.free.set(free);
+            self.capacity.set(self.capacity.get() + growth);
+            self.available.set(self.available.get() + growth);
+        }
+
+        // Take the first free slot
+        let slot = self.free.get();
+        self.free.set(unsafe { (*slot).next });
+        self.available.set(self.available.get() - 1);
+
+        // Move the value into the slot
+        unsafe { slot.write(Slot { value: ManuallyDrop::new(value) }) };
+        let slot = unsafe { NonNull::new_unchecked(slot) };
+        Ok(PoolBox { pool: self, slot, _value: PhantomData })
+    }
+
+    /// Returns a slot to the free list
+    ///
+    /// # Safety
+    /// `slot` must belong to this pool, and its value must have been moved out or dropped.
+    unsafe fn release(&self, slot: NonNull<Slot<T>>) {
+        slot.as_ptr().write(Slot { next: self.free.get() });
+        self.free.set(slot.as_ptr());
+        self.available.set(self.available.get() + 1);
+    }
+}
+impl<T> Debug for HeapPool<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("HeapPool").field("capacity", &self.capacity()).field("available", &self.available()).finish()
+    }
+}
+impl<T> Drop for HeapPool<T> {
+    fn drop(&mut self) {
+        // Release all chunks; all values have been dropped since the boxes borrow the pool
+        let mut chunk = self.chunks.get();
+        while let Some(current) = NonNull::new(chunk) {
+            chunk = unsafe { ChunkHeader::free::<T>(current) };
+        }
+    }
+}
+
+/// A value that lives in a `HeapPool` slot and returns the slot to the pool on drop
+pub struct PoolBox<'a, T> {
+    /// The pool that owns the slot
+    pool: &'a HeapPool<T>,
+    /// The slot
+    slot: NonNull<Slot<T>>,
+    /// The owned value
+    _value: PhantomData<T>,
+}
+impl<T> PoolBox<'_, T> {
+    /// Moves the value out of the slot and returns the slot to the pool
+    pub fn into_inner(self) -> T {
+        let this = ManuallyDrop::new(self);
+        let value = unsafe { ManuallyDrop::take(&mut (*this.slot.as_ptr()).value) };
+        unsafe { this.pool.release(this.slot) };
+        value
+    }
+}
+impl<T> Deref for PoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &(*self.slot.as_ptr()).value }
+    }
+}
+impl<T> DerefMut for PoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut (*self.slot.as_ptr()).value }
+    }
+}
+impl<T> AsRef<T> for PoolBox<'_, T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+impl<T> AsMut<T> for PoolBox<'_, T> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+impl<T> Debug for PoolBox<'_, T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        T::fmt(self, f)
+    }
+}
+impl<T> Display for PoolBox<'_, T>
+where
+    T: Display,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        T::fmt(self, f)
+    }
+}
+impl<T> Drop for PoolBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut (*self.slot.as_ptr()).value) };
+        unsafe { self.pool.release(self.slot) };
+    }
+}
@@ -0,0 +1,167 @@
+//! A `malloc`/`free`-managed single-producer/single-consumer queue that can be shared between cores
+//!
+//! # Synchronization
+//! Since the RP2040 does not support atomic read-modify-write operations, the queue uses two 32-bit indices where each
+//! index is only written by one side: the producer advances the tail and the consumer advances the head. The indices
+//! are accessed via volatile loads and stores, and fences ensure that the elements are written before they are
+//! published and read before their slots are released.
+//!
+//! # Indices
+//! The indices wrap at `2 * LEN` instead of `2^32`, so that they map to the same slot before and after wrapping for
+//! every capacity, while a full and an empty queue can still be distinguished.
+
+use crate::{error::AllocError, heap::Heap};
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr,
+    sync::atomic::{self, Ordering},
+};
+
+/// The shared state of the queue
+struct Shared<T, const LEN: usize> {
+    /// The amount of popped elements modulo `2 * LEN`, which is only written by the consumer
+    head: UnsafeCell<u32>,
+    /// The amount of pushed elements modulo `2 * LEN`, which is only written by the producer
+    tail: UnsafeCell<u32>,
+    /// The element slots
+    slots: [UnsafeCell<MaybeUninit<T>>; LEN],
+}
+impl<T, const LEN: usize> Shared<T, LEN> {
+    /// The modulus of the indices
+    const WRAP: u32 = 2 * LEN as u32;
+
+    /// Loads the head index
+    fn head(&self) -> u32 {
+        unsafe { self.head.get().read_volatile() }
+    }
+    /// Loads the tail index
+    fn tail(&self) -> u32 {
+        unsafe { self.tail.get().read_volatile() }
+    }
+    /// The amount of elements in the queue
+    fn len(&self) -> usize {
+        Self::distance(self.head(), self.tail()) as usize
+    }
+    /// The distance from index `from` to index `to`
+    fn distance(from: u32, to: u32) -> u32 {
+        (to + Self::WRAP - from) % Self::WRAP
+    }
+    /// The index that follows `index` after `offset` steps
+    fn advance(index: u32, offset: u32) -> u32 {
+        (index + offset) % Self::WRAP
+    }
+    /// A pointer to the slot for the given index
+    fn slot(&self, index: u32) -> *mut MaybeUninit<T> {
+        self.slots[index as usize % LEN].get()
+    }
+}
+
+/// A heap-allocated single-producer/single-consumer queue with a capacity of `LEN` elements
+pub struct HeapQueue<T, const LEN: usize> {
+    /// The shared state
+    shared: Heap<Shared<T, LEN>>,
+}
+impl<T, const LEN: usize> HeapQueue<T, LEN> {
+    /// Creates a new empty queue
+    pub fn new() -> Result<Self, AllocError> {
+        // Validate the capacity so that all index computations fit into 32 bits
+        const { assert!(LEN > 0 && LEN <= 1 << 30, "invalid queue capacity") };
+
+        // Allocate the memory and initialize the indices; the slots may stay uninitialized
+        let mut shared = Heap::<MaybeUninit<Shared<T, LEN>>>::new_uninit().ok_or(AllocError)?;
+        let memory = shared.as_mut_ptr();
+        unsafe { ptr::addr_of_mut!((*memory).head).write(UnsafeCell::new(0)) };
+        unsafe { ptr::addr_of_mut!((*memory).tail).write(UnsafeCell::new(0)) };
+        Ok(Self { shared: unsafe { shared.assume_init() } })
+    }
+
+    /// The capacity of the queue
+    pub const fn capacity(&self) -> usize {
+        LEN
+    }
+    /// The amount of elements in the queue
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+    /// Whether the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Splits the queue into a producer and a consumer handle, which can be moved to different cores
+    pub fn split(&mut self) -> (Producer<'_, T, LEN>, Consumer<'_, T, LEN>) {
+        let shared = self.shared.inner();
+        (Producer { shared, marker: PhantomData }, Consumer { shared, marker: PhantomData })
+    }
+}
+impl<T, const LEN: usize> Drop for HeapQueue<T, LEN> {
+    fn drop(&mut self) {
+        // Drop the remaining elements; the memory itself is released by the `Heap`
+        let shared = self.shared.inner();
+        for offset in 0..shared.len() as u32 {
+            unsafe { (*shared.slot(Shared::<T, LEN>::advance(shared.head(), offset))).assume_init_drop() };
+        }
+    }
+}
+
+/// The producer handle of a `HeapQueue`
+pub struct Producer<'a, T, const LEN: usize> {
+    /// The shared state
+    shared: &'a Shared<T, LEN>,
+    /// A marker to make the handle `!Sync`
+    marker: PhantomData<*mut T>,
+}
+impl<T, const LEN: usize> Producer<'_, T, LEN> {
+    /// Appends `value` to the queue or returns it as error if the queue is full
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        // Ensure that there is a free slot
+        let tail = self.shared.tail();
+        if Shared::<T, LEN>::distance(self.shared.head(), tail) as usize == LEN {
+            return Err(value);
+        }
+
+        // Ensure that the consumer has released the slot before it is written, and publish the element afterwards
+        atomic::fence(Ordering::Acquire);
+        unsafe { (*self.shared.slot(tail)).write(value) };
+        atomic::fence(Ordering::Release);
+        unsafe { self.shared.tail.get().write_volatile(Shared::<T, LEN>::advance(tail, 1)) };
+        Ok(())
+    }
+    /// Whether the queue is full
+    pub fn is_full(&self) -> bool {
+        self.shared.len() == LEN
+    }
+}
+unsafe impl<T, const LEN: usize> Send for Producer<'_, T, LEN> where T: Send {}
+
+/// The consumer handle of a `HeapQueue`
+pub struct Consumer<'a, T, const LEN: usize> {
+    /// The shared state
+    shared: &'a Shared<T, LEN>,
+    /// A marker to make the handle `!Sync`
+    marker: PhantomData<*mut T>,
+}
+impl<T, const LEN: usize> Consumer<'_, T, LEN> {
+    /// Removes the oldest element from the queue
+    pub fn pop(&mut self) -> Option<T> {
+        // Ensure that there is an element
+        let head = self.shared.head();
+        if self.shared.tail() == head {
+            return None;
+        }
+
+        // Ensure that the element has been published before it is read, and release the slot afterwards
+        atomic::fence(Ordering::Acquire);
+        let value = unsafe { (*self.shared.slot(head)).assume_init_read() };
+        atomic::fence(Ordering::Release);
+        unsafe { self.shared.head.get().write_volatile(Shared::<T, LEN>::advance(head, 1)) };
+        Some(value)
+    }
+    /// Whether the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.shared.len() == 0
+    }
+}
+unsafe impl<T, const LEN: usize> Send for Consumer<'_, T, LEN> where T: Send {}
@@ -1,25 +1,31 @@
 //! A `malloc`/`free`-managed reference-counted heap object
 
-use crate::heap::Heap;
+pub use crate::refcounter::{RefCount, OVERHEAD};
+
+use crate::{
+    error::{AllocError, RefCountOverflow},
+    heap::Heap,
+    refcounter::RefCounter,
+    trace,
+    tryclone::TryClone,
+};
 use core::{
-    fmt::{self, Debug, Formatter},
+    borrow::{Borrow, BorrowMut},
+    cmp::Ordering,
+    error::Error,
+    ffi::c_void,
+    fmt::{self, Debug, Display, Formatter, Pointer},
+    hash::{Hash, Hasher},
     mem,
-    ops::Deref,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
 };
 
-/// A shared reference counter
-struct RefCounter {
-    /// The amount of strong references
-    pub strong: usize,
-    /// The amount of weak references
-    pub weak: usize,
-}
-
-/// The memory overhead for the reference counters in bytes
-pub const OVERHEAD: usize = mem::size_of::<RefCounter>();
-
 /// A reference counted heap object
-pub struct HeapRef<T> {
+pub struct HeapRef<T>
+where
+    T: ?Sized,
+{
     /// The referenced value
     value: *mut T,
     /// The reference counter
@@ -27,144 +33,607 @@ pub struct HeapRef<T> {
 }
 impl<T> HeapRef<T> {
     /// The amount of heap-allocated memory in bytes
-    pub const SIZE: usize = mem::size_of::<T>() + OVERHEAD;
+    pub const SIZE: usize = RefCounter::size::<T>();
 
     /// Creates a new reference counted heap object from the given heap object
+    ///
+    /// # Note
+    /// Since the value shares the allocation with the reference counters, the memory of `value` is grown via `realloc`
+    /// and the value is moved behind the reference counters. This only fails if `realloc` fails, in which case `value`
+    /// is returned as error.
     pub fn new_from_heap(value: Heap<T>) -> Result<Self, Heap<T>> {
-        // Create a reference counter that resembles one strong reference
-        let refctr = RefCounter { strong: 1, weak: 0 };
-
-        // Move the reference counter to the heap
-        let refctr = match Heap::new(refctr) {
-            Ok(refctr) => refctr,
-            Err(_) => return Err(value),
-        };
-
-        Ok(Self { value: value.into_raw(), refctr: refctr.into_raw() })
+        // Grow the memory with a reference counter that resembles one strong reference
+        let memory = value.into_raw() as *mut c_void;
+        match unsafe { RefCounter::from_heap(memory, mem::size_of::<T>(), mem::align_of::<T>(), 1, 1) } {
+            Some(refctr) => Ok(unsafe { Self::from_refctr(refctr) }),
+            None => Err(unsafe { Heap::from_raw(memory as *mut T) }),
+        }
     }
     /// Creates a new reference counted heap object with the given value
     pub fn new(value: T) -> Result<Self, T> {
-        // Move the value to the heap
-        let value = Heap::new(value)?;
+        // Allocate the memory with a reference counter that resembles one strong reference
+        let Some(refctr) = RefCounter::alloc::<T>(1, 1) else {
+            return Err(value);
+        };
 
-        // Create the reference counted heap object
-        match Self::new_from_heap(value) {
-            Ok(this) => Ok(this),
-            Err(value) => Err(value.into_inner()),
-        }
+        // Initialize the value
+        let this = unsafe { Self::from_refctr(refctr) };
+        unsafe { this.value.write(value) };
+        Ok(this)
     }
+    /// Creates a new reference counted heap object with the value returned by `generator`, which gets a weak reference
+    /// to the object under construction
+    ///
+    /// # Note
+    /// Until the construction completes, the weak reference cannot be upgraded. If the allocation fails or `generator`
+    /// panics, all allocated memory is released.
+    pub fn try_new_cyclic<F>(generator: F) -> Result<Self, AllocError>
+    where
+        F: FnOnce(&HeapRefWeak<T>) -> T,
+    {
+        // Allocate the memory with a reference counter that resembles one weak reference
+        let refctr = RefCounter::alloc::<T>(0, 1).ok_or(AllocError)?;
+        let value = unsafe { RefCounter::value(refctr) };
 
-    /// A reference to the underlying value
-    pub fn inner(&self) -> &T {
-        let reference = unsafe { self.value.as_ref() };
-        reference.expect("unexpected null pointer")
-    }
+        // Create the weak reference and generate the value
+        let weak = HeapRefWeak { value, refctr };
+        let generated = generator(&weak);
+        unsafe { value.write(generated) };
 
-    /// The amount of strong references to the underlying value
-    pub fn strong(&self) -> usize {
-        unsafe { (*self.refctr).strong }
-    }
-    /// The amount of weak references to the underlying value
-    pub fn weak(&self) -> usize {
-        unsafe { (*self.refctr).weak }
+        // Register the strong reference, which takes over the temporary weak reference as implicit weak reference
+        unsafe { (*refctr).strong = 1 };
+        mem::forget(weak);
+        Ok(Self { value, refctr })
     }
 
-    /// Creates a weak reference to the heap allocated object
-    pub fn downgrade(&self) -> HeapRefWeak<T> {
-        unsafe { (*self.refctr).weak += 1 };
-        HeapRefWeak { value: self.value, refctr: self.refctr }
+    /// A mutable reference to the underlying value, which is cloned first if there are other strong references to it
+    ///
+    /// # Note
+    /// Like `Rc::make_mut`, this function disassociates all weak references if `self` is the last strong reference; i.e.
+    /// they cannot be upgraded anymore.
+    pub fn try_make_mut(&mut self) -> Result<&mut T, AllocError>
+    where
+        T: Clone,
+    {
+        if self.strong() > 1 {
+            // Allocate the memory and clone the value into it
+            let refctr = RefCounter::alloc::<T>(0, 1).ok_or(AllocError)?;
+            let weak = HeapRefWeak { value: unsafe { RefCounter::value::<T>(refctr) }, refctr };
+            unsafe { weak.value.write(self.inner().clone()) };
+
+            // Register the strong reference, which takes over the temporary weak reference, and replace `self`
+            unsafe { (*refctr).strong = 1 };
+            *self = Self { value: weak.value, refctr };
+            mem::forget(weak);
+        } else if self.weak() > 0 {
+            // Allocate the memory and move the value
+            let refctr = RefCounter::alloc::<T>(1, 1).ok_or(AllocError)?;
+            let this = unsafe { Self::from_refctr(refctr) };
+            unsafe { ptr::copy_nonoverlapping(self.value, this.value, 1) };
+
+            // Replace `self` and release the old strong reference without dropping the moved value
+            let old = mem::replace(self, this);
+            unsafe { old.release_moved() };
+        }
+
+        // We are the only reference now
+        let reference = self.get_mut();
+        Ok(reference.expect("unexpected shared reference"))
     }
 
     /// Returns the underlying element as heap-object
+    ///
+    /// # Note
+    /// If `self` is the only reference, the value is moved to the start of the memory, which is then shrunk via
+    /// `realloc` on a best-effort basis. If there are weak references left, they keep the memory alive, so the value is
+    /// copied into a newly allocated heap object instead. If this allocation fails, `self` is returned as error.
     pub fn try_unwrap_heap(self) -> Result<Heap<T>, Self> {
         // Ensure that we are the last strong reference
         if self.strong() > 1 {
             return Err(self);
         }
 
-        // Take the value and set the reference counter to zero
-        let value = unsafe { Heap::from_raw(self.value) };
-        unsafe { (*self.refctr).strong = 0 };
-
-        // Deallocate the reference counter if there are no weak references left
+        // Reuse the memory if there are no weak references
         if self.weak() == 0 {
-            let refctr = unsafe { Heap::from_raw(self.refctr) };
-            drop(refctr);
+            let memory = unsafe { RefCounter::into_heap(self.refctr, mem::size_of::<T>(), mem::align_of::<T>()) };
+            mem::forget(self);
+            return Ok(unsafe { Heap::from_raw(memory as *mut T) });
         }
 
-        // Forget `self` to avoid double-free during `drop()`
-        mem::forget(self);
-        Ok(value)
+        // Allocate the heap object and move the value
+        let Some(mut value) = Heap::new_uninit() else {
+            return Err(self);
+        };
+        unsafe { ptr::copy_nonoverlapping(self.value, value.as_mut_ptr(), 1) };
+
+        // Release the strong reference without dropping the value
+        unsafe { self.release_moved() };
+        Ok(unsafe { value.assume_init() })
+    }
+    /// Returns the underlying element if `self` is the last strong reference, or releases `self` otherwise
+    pub fn into_inner(self) -> Option<T> {
+        self.try_unwrap().ok()
+    }
+    /// Returns the underlying element if `self` is the last strong reference, or a clone of it otherwise
+    pub fn unwrap_or_clone(self) -> T
+    where
+        T: Clone,
+    {
+        match self.try_unwrap() {
+            Ok(value) => value,
+            Err(this) => this.inner().clone(),
+        }
     }
     /// Returns the underlying element
     pub fn try_unwrap(self) -> Result<T, Self> {
-        let value = self.try_unwrap_heap()?;
-        Ok(value.into_inner())
+        // Ensure that we are the last strong reference
+        if self.strong() > 1 {
+            return Err(self);
+        }
+
+        // Take the value and release the strong reference without dropping the value
+        let value = unsafe { self.value.read() };
+        unsafe { self.release_moved() };
+        Ok(value)
+    }
+
+    /// Creates a new instance from a reference counter that has been allocated via `RefCounter::alloc`
+    ///
+    /// # Safety
+    /// This function is unsafe because the reference counter is not modified.
+    unsafe fn from_refctr(refctr: *mut RefCounter) -> Self {
+        Self { value: RefCounter::value(refctr), refctr }
+    }
+    /// Releases the last strong reference after the value has been moved out of the allocation
+    ///
+    /// # Safety
+    /// This function is unsafe because the value is not dropped; i.e. it must have been moved before.
+    unsafe fn release_moved(self) {
+        // Set the reference counter to zero and release the implicit weak reference
+        (*self.refctr).strong = 0;
+        RefCounter::release_weak(self.refctr, self.value);
+
+        // Forget `self` to avoid double-free during `drop()`
+        mem::forget(self);
+    }
+}
+impl<T> HeapRef<[T]> {
+    /// Creates a new reference counted heap slice with clones of the given values
+    pub fn new_from_slice(values: &[T]) -> Result<Self, AllocError>
+    where
+        T: TryClone,
+    {
+        // Allocate the memory and clone the values
+        let mut writer = SliceWriter::new(values.len()).ok_or(AllocError)?;
+        for value in values {
+            writer.push(value.try_clone()?);
+        }
+        Ok(writer.finish())
+    }
+    /// Creates a new reference counted heap slice with the values yielded by `iter`
+    ///
+    /// # Panics
+    /// This function panics if the iterator yields less elements than announced via `ExactSizeIterator::len`.
+    pub fn new_from_iter<I>(iter: I) -> Result<Self, AllocError>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        // Allocate the memory and collect the values
+        let iter = iter.into_iter();
+        let len = iter.len();
+        let mut writer = SliceWriter::new(len).ok_or(AllocError)?;
+        iter.take(len).for_each(|value| writer.push(value));
+        Ok(writer.finish())
+    }
+    /// Creates a new reference counted heap slice with the values yielded by `iter`
+    ///
+    /// # Note
+    /// The memory is allocated according to the lower bound of `Iterator::size_hint`, so that exactly sized iterators
+    /// need a single allocation. Additional elements grow the memory via `realloc`, and excess memory is released at
+    /// the end on a best-effort basis. If an allocation fails, the already collected elements are dropped and the memory
+    /// is released.
+    pub fn try_from_iter<I>(iter: I) -> Result<Self, AllocError>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        // Allocate the memory according to the size hint
+        let iter = iter.into_iter();
+        let (len, _) = iter.size_hint();
+        let mut writer = SliceWriter::new(len).ok_or(AllocError)?;
+
+        // Collect the elements and release the excess memory
+        for value in iter {
+            writer.try_push_grow(value)?;
+        }
+        writer.shrink_to_fit();
+        Ok(writer.finish())
+    }
+    /// Creates a new reference counted heap slice from the given heap slice
+    ///
+    /// # Note
+    /// Since the values share the allocation with the reference counters, the memory of `values` is grown via `realloc`
+    /// and the values are moved behind the reference counters. This only fails if `realloc` fails, in which case
+    /// `values` is returned as error.
+    pub fn new_from_heap_slice(values: Heap<[T]>) -> Result<Self, Heap<[T]>> {
+        // Grow the memory with a reference counter that resembles one strong reference
+        let (len, size) = (values.len(), mem::size_of_val(values.inner()));
+        let memory = values.into_raw();
+        match unsafe { RefCounter::from_heap(memory as *mut c_void, size, mem::align_of::<T>(), 1, 1) } {
+            Some(refctr) => Ok(Self { value: unsafe { RefCounter::slice::<T>(refctr, len) }, refctr }),
+            None => Err(unsafe { Heap::from_raw(memory) }),
+        }
+    }
+}
+impl HeapRef<str> {
+    /// Creates a new reference counted heap string with a copy of the given string
+    pub fn new_from_str(value: &str) -> Result<Self, AllocError> {
+        let bytes = HeapRef::<[u8]>::new_from_slice(value.as_bytes())?;
+        Ok(unsafe { Self::from_utf8_unchecked(bytes) })
+    }
+    /// Creates a new reference counted heap string from the given heap string
+    ///
+    /// # Note
+    /// Like `HeapRef::new_from_heap_slice`, this grows the memory of `value` via `realloc` and only fails if `realloc`
+    /// fails.
+    pub fn new_from_heap_str(value: Heap<str>) -> Result<Self, Heap<str>> {
+        // Reinterpret the string as bytes
+        let bytes = Heap::<[u8]>::from(value);
+        match HeapRef::<[u8]>::new_from_heap_slice(bytes) {
+            Ok(bytes) => Ok(unsafe { Self::from_utf8_unchecked(bytes) }),
+            Err(bytes) => Err(unsafe { Heap::from_raw(bytes.into_raw() as *mut str) }),
+        }
+    }
+
+    /// Reinterprets the given bytes as string
+    ///
+    /// # Safety
+    /// This function is unsafe because the bytes must be valid UTF-8.
+    unsafe fn from_utf8_unchecked(bytes: HeapRef<[u8]>) -> Self {
+        let (value, refctr) = (bytes.value as *mut str, bytes.refctr);
+        mem::forget(bytes);
+        Self { value, refctr }
+    }
+}
+impl<T> HeapRef<T>
+where
+    T: ?Sized,
+{
+    /// Recreates a reference counted heap object from a raw pointer that has been created with `HeapRef::into_raw`
+    ///
+    /// # Safety
+    /// This function is unsafe because improper use may lead to memory problems. For example, a double-free may occur if
+    /// the function is called twice on the same raw pointer.
+    pub unsafe fn from_raw(value: *const T) -> Self {
+        assert!(!value.is_null(), "unexpected null pointer");
+        let value = value as *mut T;
+        Self { value, refctr: RefCounter::from_value(value) }
+    }
+    /// Consumes `self` and returns a raw pointer to the underlying value
+    ///
+    /// # Note
+    /// The strong reference is not released. To release it, recreate a `HeapRef` from the raw pointer with
+    /// `HeapRef::from_raw` and drop it accordingly, or use `HeapRef::decrement_strong_count`.
+    pub fn into_raw(self) -> *const T {
+        let value = self.value;
+        mem::forget(self);
+        value
+    }
+    /// Increments the strong reference count of the object that `value` points to
+    ///
+    /// # Panics
+    /// This function panics if the strong reference count would overflow.
+    ///
+    /// # Safety
+    /// `value` must have been obtained via `HeapRef::into_raw` and the associated strong reference must still be alive.
+    pub unsafe fn increment_strong_count(value: *const T) {
+        let refctr = RefCounter::from_value(value as *mut T);
+        RefCounter::increment_strong(refctr).expect("reference count overflow");
+    }
+    /// Decrements the strong reference count of the object that `value` points to and releases it if necessary
+    ///
+    /// # Safety
+    /// `value` must have been obtained via `HeapRef::into_raw` and the associated strong reference must still be alive.
+    /// The associated strong reference is released by this call.
+    pub unsafe fn decrement_strong_count(value: *const T) {
+        drop(Self::from_raw(value));
+    }
+
+    /// A raw pointer to the underlying value
+    pub fn as_ptr(&self) -> *const T {
+        self.value
+    }
+    /// Whether `self` and `other` reference the same object
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        ptr::eq(self.value, other.value)
+    }
+    /// A reference to the underlying value
+    pub fn inner(&self) -> &T {
+        let reference = unsafe { self.value.as_ref() };
+        reference.expect("unexpected null pointer")
+    }
+    /// A mutable reference to the underlying value if `self` is the only reference (strong or weak) to it
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // Ensure that we are the only reference
+        if self.strong() != 1 || self.weak() != 0 {
+            return None;
+        }
+
+        // Create the reference
+        let reference = unsafe { self.value.as_mut() };
+        Some(reference.expect("unexpected null pointer"))
+    }
+
+    /// The amount of strong references to the underlying value
+    pub fn strong(&self) -> usize {
+        unsafe { RefCounter::strong(self.refctr) }
+    }
+    /// The amount of weak references to the underlying value
+    pub fn weak(&self) -> usize {
+        unsafe { RefCounter::weak(self.refctr) }
+    }
+
+    /// Creates a new strong reference to the heap allocated object or returns an error if the strong reference count
+    /// would overflow
+    pub fn try_clone_ref(&self) -> Result<Self, RefCountOverflow> {
+        unsafe { RefCounter::increment_strong(self.refctr) }?;
+        Ok(Self { value: self.value, refctr: self.refctr })
+    }
+    /// Creates a weak reference to the heap allocated object
+    ///
+    /// # Panics
+    /// This function panics if the weak reference count would overflow.
+    pub fn downgrade(&self) -> HeapRefWeak<T> {
+        self.try_downgrade().expect("reference count overflow")
+    }
+    /// Creates a weak reference to the heap allocated object or returns an error if the weak reference count would
+    /// overflow
+    pub fn try_downgrade(&self) -> Result<HeapRefWeak<T>, RefCountOverflow> {
+        unsafe { RefCounter::increment_weak(self.refctr) }?;
+        Ok(HeapRefWeak { value: self.value, refctr: self.refctr })
     }
 }
-impl<T> Deref for HeapRef<T> {
+impl<T> Deref for HeapRef<T>
+where
+    T: ?Sized,
+{
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
         self.inner()
     }
 }
-impl<T> AsRef<T> for HeapRef<T> {
+impl<T> AsRef<T> for HeapRef<T>
+where
+    T: ?Sized,
+{
     fn as_ref(&self) -> &T {
         self.inner()
     }
 }
+impl<T> Borrow<T> for HeapRef<T>
+where
+    T: ?Sized,
+{
+    fn borrow(&self) -> &T {
+        self.inner()
+    }
+}
+impl<T> PartialEq for HeapRef<T>
+where
+    T: PartialEq + ?Sized,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.inner().eq(other.inner())
+    }
+}
+impl<T> Eq for HeapRef<T> where T: Eq + ?Sized {}
+impl<T> PartialOrd for HeapRef<T>
+where
+    T: PartialOrd + ?Sized,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.inner().partial_cmp(other.inner())
+    }
+}
+impl<T> Ord for HeapRef<T>
+where
+    T: Ord + ?Sized,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.inner().cmp(other.inner())
+    }
+}
+impl<T> Hash for HeapRef<T>
+where
+    T: Hash + ?Sized,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner().hash(state)
+    }
+}
 impl<T> Debug for HeapRef<T>
 where
-    T: Debug,
+    T: Debug + ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.inner().fmt(f)
+    }
+}
+impl<T> Display for HeapRef<T>
+where
+    T: Display + ?Sized,
 {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         self.inner().fmt(f)
     }
 }
-impl<T> Clone for HeapRef<T> {
+impl<T> Pointer for HeapRef<T>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Pointer::fmt(&self.value, f)
+    }
+}
+impl<T> Error for HeapRef<T>
+where
+    T: Error + ?Sized,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.inner().source()
+    }
+}
+impl<T> Clone for HeapRef<T>
+where
+    T: ?Sized,
+{
+    /// Creates a new strong reference to the heap allocated object
+    ///
+    /// # Panics
+    /// This function panics if the strong reference count would overflow.
     fn clone(&self) -> Self {
-        unsafe { (*self.refctr).strong += 1 };
-        Self { value: self.value, refctr: self.refctr }
+        self.try_clone_ref().expect("reference count overflow")
     }
 }
-impl<T> Drop for HeapRef<T> {
+impl<T> TryClone for HeapRef<T>
+where
+    T: ?Sized,
+{
+    /// Creates a new strong reference to the heap allocated object
+    ///
+    /// # Errors
+    /// A strong reference count overflow is reported as `AllocError`.
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        self.try_clone_ref().map_err(|_| AllocError)
+    }
+}
+impl<T> Drop for HeapRef<T>
+where
+    T: ?Sized,
+{
     fn drop(&mut self) {
-        // Decrement the reference counter
-        unsafe { (*self.refctr).strong -= 1 };
-
-        // Deallocate the value if we are the last strong reference
-        if self.strong() == 0 {
-            let value = unsafe { Heap::from_raw(self.value) };
-            drop(value);
-        }
-
-        // Deallocate the reference counter if we are the last reference
-        if self.strong() == 0 && self.weak() == 0 {
-            let refctr = unsafe { Heap::from_raw(self.refctr) };
-            drop(refctr);
+        // Drop the value and release the implicit weak reference if we are the last strong reference
+        if unsafe { RefCounter::decrement_strong(self.refctr) } {
+            unsafe { self.value.drop_in_place() };
+            unsafe { RefCounter::release_weak(self.refctr, self.value) };
         }
     }
 }
 
 /// A weak reference to a reference counted heap object
-pub struct HeapRefWeak<T> {
+pub struct HeapRefWeak<T>
+where
+    T: ?Sized,
+{
     /// The referenced value
     value: *mut T,
     /// The reference counter
     refctr: *mut RefCounter,
 }
 impl<T> HeapRefWeak<T> {
+    /// Creates a new dangling weak reference that cannot be upgraded
+    ///
+    /// # Note
+    /// This function does not allocate any memory.
+    pub const fn new() -> Self {
+        Self { value: NonNull::dangling().as_ptr(), refctr: ptr::null_mut() }
+    }
+
+    /// Recreates a weak reference from a raw pointer that has been created with `HeapRefWeak::into_raw`
+    ///
+    /// # Safety
+    /// This function is unsafe because improper use may lead to memory problems. For example, a double-free may occur if
+    /// the function is called twice on the same raw pointer.
+    pub unsafe fn from_raw(value: *const T) -> Self {
+        // Recreate dangling references as-is
+        let value = value as *mut T;
+        if value == NonNull::dangling().as_ptr() {
+            return Self::new();
+        }
+
+        // Recreate the reference
+        assert!(!value.is_null(), "unexpected null pointer");
+        Self { value, refctr: RefCounter::from_sized_value(value) }
+    }
+    /// Consumes `self` and returns a raw pointer to the underlying value
+    ///
+    /// # Note
+    /// The weak reference is not released. To release it, recreate a `HeapRefWeak` from the raw pointer with
+    /// `HeapRefWeak::from_raw` and drop it accordingly, or use `HeapRefWeak::decrement_weak_count`. Since the value may
+    /// have been dropped already, the pointer must not be dereferenced unless there is a strong reference left.
+    pub fn into_raw(self) -> *const T {
+        let value = self.value;
+        mem::forget(self);
+        value
+    }
+    /// Increments the weak reference count of the object that `value` points to
+    ///
+    /// # Panics
+    /// This function panics if the weak reference count would overflow.
+    ///
+    /// # Safety
+    /// `value` must have been obtained via `HeapRefWeak::into_raw` and the associated weak reference must still be
+    /// alive.
+    pub unsafe fn increment_weak_count(value: *const T) {
+        let this = mem::ManuallyDrop::new(Self::from_raw(value));
+        let clone = (*this).clone();
+        mem::forget(clone);
+    }
+    /// Decrements the weak reference count of the object that `value` points to and releases it if necessary
+    ///
+    /// # Safety
+    /// `value` must have been obtained via `HeapRefWeak::into_raw` and the associated weak reference must still be
+    /// alive. The associated weak reference is released by this call.
+    pub unsafe fn decrement_weak_count(value: *const T) {
+        drop(Self::from_raw(value));
+    }
+}
+impl<T> HeapRefWeak<T>
+where
+    T: ?Sized,
+{
+    /// A raw pointer to the underlying value
+    ///
+    /// # Note
+    /// Since the value may have been dropped already, the pointer must not be dereferenced unless there is a strong
+    /// reference left.
+    pub fn as_ptr(&self) -> *const T {
+        self.value
+    }
+    /// Whether `self` and `other` reference the same object, or whether both are dangling
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        ptr::eq(self.value, other.value)
+    }
+
     /// The amount of strong references to the underlying value
     pub fn strong(&self) -> usize {
-        unsafe { (*self.refctr).strong }
+        if self.is_dangling() {
+            return 0;
+        }
+        unsafe { RefCounter::strong(self.refctr) }
     }
     /// The amount of weak references to the underlying value
     pub fn weak(&self) -> usize {
-        unsafe { (*self.refctr).weak }
+        if self.is_dangling() {
+            return 0;
+        }
+        unsafe { RefCounter::weak(self.refctr) }
     }
 
+    /// Creates a new weak reference to the heap allocated object or returns an error if the weak reference count would
+    /// overflow
+    pub fn try_clone_ref(&self) -> Result<Self, RefCountOverflow> {
+        // Increment the reference counter if any
+        if !self.is_dangling() {
+            unsafe { RefCounter::increment_weak(self.refctr) }?;
+        }
+        Ok(Self { value: self.value, refctr: self.refctr })
+    }
     /// Tries to create a strong reference to the heap object
+    ///
+    /// # Panics
+    /// This function panics if the strong reference count would overflow.
     pub fn upgrade(&self) -> Option<HeapRef<T>> {
         // Ensure that there is at least one strong reference left
         if self.strong() == 0 {
@@ -172,25 +641,320 @@ impl<T> HeapRefWeak<T> {
         }
 
         // Update the reference counter and create the reference
-        unsafe { (*self.refctr).strong += 1 };
+        unsafe { RefCounter::increment_strong(self.refctr) }.expect("reference count overflow");
         Some(HeapRef { value: self.value, refctr: self.refctr })
     }
+
+    /// Whether `self` has been created by `HeapRefWeak::new` and thus has no reference counter
+    fn is_dangling(&self) -> bool {
+        self.refctr.is_null()
+    }
 }
-impl<T> Clone for HeapRefWeak<T> {
+impl<T> Default for HeapRefWeak<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T> Clone for HeapRefWeak<T>
+where
+    T: ?Sized,
+{
+    /// Creates a new weak reference to the heap allocated object
+    ///
+    /// # Panics
+    /// This function panics if the weak reference count would overflow.
     fn clone(&self) -> Self {
-        unsafe { (*self.refctr).weak += 1 };
-        Self { value: self.value, refctr: self.refctr }
+        self.try_clone_ref().expect("reference count overflow")
+    }
+}
+impl<T> TryClone for HeapRefWeak<T>
+where
+    T: ?Sized,
+{
+    /// Creates a new weak reference to the heap allocated object
+    ///
+    /// # Errors
+    /// A weak reference count overflow is reported as `AllocError`.
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        self.try_clone_ref().map_err(|_| AllocError)
+    }
+}
+impl<T> Drop for HeapRefWeak<T>
+where
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        // Dangling references have no reference counter
+        if self.is_dangling() {
+            return;
+        }
+
+        // Decrement the reference counter and deallocate the memory if we are the last reference
+        unsafe { RefCounter::release_weak(self.refctr, self.value) };
+    }
+}
+
+/// A uniquely owned heap object with the memory layout of a `HeapRef`, which can be converted into a `HeapRef` without
+/// reallocation
+///
+/// # Note
+/// Weak references can be created at any time; however they cannot be upgraded until the object has been converted via
+/// `UniqueHeapRef::into_shared`.
+pub struct UniqueHeapRef<T> {
+    /// The referenced value
+    value: *mut T,
+    /// The reference counter
+    refctr: *mut RefCounter,
+}
+impl<T> UniqueHeapRef<T> {
+    /// The amount of heap-allocated memory in bytes
+    pub const SIZE: usize = RefCounter::size::<T>();
+
+    /// Creates a new unique heap object from the given heap object
+    ///
+    /// # Note
+    /// Since the value shares the allocation with the reference counters, the memory of `value` is grown via `realloc`
+    /// and the value is moved behind the reference counters. This only fails if `realloc` fails, in which case `value`
+    /// is returned as error.
+    pub fn new_from_heap(value: Heap<T>) -> Result<Self, Heap<T>> {
+        // Grow the memory with a reference counter that resembles no strong reference
+        let memory = value.into_raw() as *mut c_void;
+        match unsafe { RefCounter::from_heap(memory, mem::size_of::<T>(), mem::align_of::<T>(), 0, 1) } {
+            Some(refctr) => Ok(Self { value: unsafe { RefCounter::value(refctr) }, refctr }),
+            None => Err(unsafe { Heap::from_raw(memory as *mut T) }),
+        }
+    }
+    /// Creates a new unique heap object with the given value
+    pub fn new(value: T) -> Result<Self, T> {
+        // Allocate the memory with a reference counter that resembles no strong reference
+        let Some(refctr) = RefCounter::alloc::<T>(0, 1) else {
+            return Err(value);
+        };
+
+        // Initialize the value
+        let this = Self { value: unsafe { RefCounter::value(refctr) }, refctr };
+        unsafe { this.value.write(value) };
+        Ok(this)
+    }
+
+    /// A reference to the underlying value
+    pub fn inner(&self) -> &T {
+        let reference = unsafe { self.value.as_ref() };
+        reference.expect("unexpected null pointer")
+    }
+    /// A mutable reference to the underlying value
+    pub fn inner_mut(&mut self) -> &mut T {
+        let reference = unsafe { self.value.as_mut() };
+        reference.expect("unexpected null pointer")
+    }
+
+    /// The amount of weak references to the underlying value
+    #[allow(clippy::unnecessary_cast, reason = "the counter type depends on the enabled features")]
+    pub fn weak(&self) -> usize {
+        // Exclude the weak reference that is held by `self`
+        unsafe { (*self.refctr).weak as usize - 1 }
+    }
+    /// Creates a weak reference to the heap allocated object, which cannot be upgraded until `self` is converted via
+    /// `UniqueHeapRef::into_shared`
+    ///
+    /// # Panics
+    /// This function panics if the weak reference count would overflow.
+    pub fn downgrade(&self) -> HeapRefWeak<T> {
+        self.try_downgrade().expect("reference count overflow")
+    }
+    /// Creates a weak reference to the heap allocated object or returns an error if the weak reference count would
+    /// overflow
+    pub fn try_downgrade(&self) -> Result<HeapRefWeak<T>, RefCountOverflow> {
+        unsafe { RefCounter::increment_weak(self.refctr) }?;
+        Ok(HeapRefWeak { value: self.value, refctr: self.refctr })
+    }
+
+    /// Converts `self` into a shared reference without reallocation
+    pub fn into_shared(self) -> HeapRef<T> {
+        // Register the strong reference, which takes over our weak reference as implicit weak reference
+        let (value, refctr) = (self.value, self.refctr);
+        unsafe { (*refctr).strong = 1 };
+        mem::forget(self);
+        HeapRef { value, refctr }
+    }
+    /// Returns the underlying element
+    pub fn into_inner(self) -> T {
+        // Take the value and release our weak reference without dropping the value
+        let value = unsafe { self.value.read() };
+        unsafe { RefCounter::release_weak(self.refctr, self.value) };
+        mem::forget(self);
+        value
+    }
+}
+impl<T> From<UniqueHeapRef<T>> for HeapRef<T> {
+    fn from(value: UniqueHeapRef<T>) -> Self {
+        value.into_shared()
+    }
+}
+impl<T> Deref for UniqueHeapRef<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner()
+    }
+}
+impl<T> DerefMut for UniqueHeapRef<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner_mut()
+    }
+}
+impl<T> AsRef<T> for UniqueHeapRef<T> {
+    fn as_ref(&self) -> &T {
+        self.inner()
+    }
+}
+impl<T> AsMut<T> for UniqueHeapRef<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self.inner_mut()
     }
 }
-impl<T> Drop for HeapRefWeak<T> {
+impl<T> Borrow<T> for UniqueHeapRef<T> {
+    fn borrow(&self) -> &T {
+        self.inner()
+    }
+}
+impl<T> BorrowMut<T> for UniqueHeapRef<T> {
+    fn borrow_mut(&mut self) -> &mut T {
+        self.inner_mut()
+    }
+}
+impl<T> PartialEq for UniqueHeapRef<T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.inner().eq(other.inner())
+    }
+}
+impl<T> Eq for UniqueHeapRef<T> where T: Eq {}
+impl<T> PartialOrd for UniqueHeapRef<T>
+where
+    T: PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.inner().partial_cmp(other.inner())
+    }
+}
+impl<T> Ord for UniqueHeapRef<T>
+where
+    T: Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.inner().cmp(other.inner())
+    }
+}
+impl<T> Hash for UniqueHeapRef<T>
+where
+    T: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner().hash(state)
+    }
+}
+impl<T> Debug for UniqueHeapRef<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.inner().fmt(f)
+    }
+}
+impl<T> Display for UniqueHeapRef<T>
+where
+    T: Display,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.inner().fmt(f)
+    }
+}
+impl<T> Pointer for UniqueHeapRef<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Pointer::fmt(&self.value, f)
+    }
+}
+impl<T> Drop for UniqueHeapRef<T> {
     fn drop(&mut self) {
-        // Decrement the reference counter
-        unsafe { (*self.refctr).weak -= 1 };
+        // Drop the value and release our weak reference
+        unsafe { self.value.drop_in_place() };
+        unsafe { RefCounter::release_weak(self.refctr, self.value) };
+    }
+}
+
+/// A writer to initialize a reference counted heap slice that drops the already initialized elements and releases the
+/// memory if it is dropped before completion (e.g. due to an early return or a panic)
+struct SliceWriter<T> {
+    /// The reference counter of the allocated memory
+    refctr: *mut RefCounter,
+    /// The first element
+    value: *mut T,
+    /// The amount of allocated elements
+    len: usize,
+    /// The amount of initialized elements
+    initialized: usize,
+}
+impl<T> SliceWriter<T> {
+    /// Allocates the memory for `len` elements with a reference counter that resembles one strong reference
+    pub fn new(len: usize) -> Option<Self> {
+        let refctr = RefCounter::alloc_slice::<T>(len, 1, 1)?;
+        Some(Self { refctr, value: unsafe { RefCounter::value(refctr) }, len, initialized: 0 })
+    }
+    /// Appends the next element
+    pub fn push(&mut self, value: T) {
+        assert!(self.initialized < self.len, "slice is already initialized");
+        unsafe { self.value.add(self.initialized).write(value) };
+        self.initialized += 1;
+    }
+    /// Appends the next element and grows the slice if necessary
+    pub fn try_push_grow(&mut self, value: T) -> Result<(), AllocError> {
+        // Double the capacity if the slice is full
+        if self.initialized == self.len {
+            let len = self.len.checked_mul(2).ok_or(AllocError)?.max(4);
+            self.try_realloc(len)?;
+        }
+
+        // Append the element
+        self.push(value);
+        Ok(())
+    }
+    /// Shrinks the slice to the initialized elements; if releasing the excess memory fails, the memory is kept
+    pub fn shrink_to_fit(&mut self) {
+        // Ensure that there is excess memory
+        if self.initialized == self.len {
+            return;
+        }
 
-        // Deallocate the reference counter if we are the last reference
-        if self.strong() == 0 && self.weak() == 0 {
-            let refctr = unsafe { Heap::from_raw(self.refctr) };
-            drop(refctr);
+        // Keep the memory but trace only the initialized elements if the reallocation fails
+        if self.try_realloc(self.initialized).is_err() {
+            let excess = mem::size_of::<T>() * (self.len - self.initialized);
+            unsafe { trace::decrement_allocated(excess) };
+            self.len = self.initialized;
         }
     }
+
+    /// Resizes the slice to `len` elements via `realloc`; if the reallocation fails, the slice is left unchanged
+    fn try_realloc(&mut self, len: usize) -> Result<(), AllocError> {
+        self.refctr = unsafe { RefCounter::realloc_slice::<T>(self.refctr, self.len, len) }.ok_or(AllocError)?;
+        self.value = unsafe { RefCounter::value(self.refctr) };
+        self.len = len;
+        Ok(())
+    }
+    /// Finishes the initialization and returns the reference counted slice
+    pub fn finish(self) -> HeapRef<[T]> {
+        assert_eq!(self.initialized, self.len, "slice is not fully initialized");
+        let this = HeapRef { value: ptr::slice_from_raw_parts_mut(self.value, self.len), refctr: self.refctr };
+        mem::forget(self);
+        this
+    }
+}
+impl<T> Drop for SliceWriter<T> {
+    fn drop(&mut self) {
+        // Drop the initialized elements and release the memory
+        unsafe { ptr::slice_from_raw_parts_mut(self.value, self.initialized).drop_in_place() };
+        unsafe { RefCounter::free(self.refctr, ptr::slice_from_raw_parts(self.value, self.len)) };
+    }
 }
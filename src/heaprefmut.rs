@@ -0,0 +1,326 @@
+//! A `malloc`/`free`-managed reference-counted heap object with interior mutability
+//!
+//! # Borrow flag
+//! The borrow flag is stored together with the value and uses the `RefCount` integer type, so that the
+//! `refcount-u16` and `refcount-u32` features also narrow the borrow flag.
+
+pub use crate::refcounter::RefCount;
+
+use crate::{
+    error::{AllocError, BorrowError, BorrowMutError, RefCountOverflow},
+    heapref::{HeapRef, HeapRefWeak},
+    tryclone::TryClone,
+};
+use core::{
+    cell::{Cell, UnsafeCell},
+    fmt::{self, Debug, Display, Formatter, Pointer},
+    ops::{Deref, DerefMut},
+};
+
+/// The borrow flag value that marks an exclusive borrow
+const EXCLUSIVE: RefCount = RefCount::MAX;
+
+/// A value with a borrow flag
+struct BorrowCell<T> {
+    /// The borrow flag; i.e. the amount of shared borrows or `EXCLUSIVE`
+    borrow: Cell<RefCount>,
+    /// The value
+    value: UnsafeCell<T>,
+}
+
+/// A reference counted heap object with interior mutability
+pub struct HeapRefMut<T> {
+    /// The underlying reference counted cell
+    cell: HeapRef<BorrowCell<T>>,
+}
+impl<T> HeapRefMut<T> {
+    /// The amount of heap-allocated memory in bytes
+    pub const SIZE: usize = HeapRef::<BorrowCell<T>>::SIZE;
+
+    /// Creates a new reference counted heap object with the given value
+    pub fn new(value: T) -> Result<Self, T> {
+        let cell = BorrowCell { borrow: Cell::new(0), value: UnsafeCell::new(value) };
+        match HeapRef::new(cell) {
+            Ok(cell) => Ok(Self { cell }),
+            Err(cell) => Err(cell.value.into_inner()),
+        }
+    }
+
+    /// Immutably borrows the underlying value
+    ///
+    /// # Panics
+    /// This function panics if the value is currently mutably borrowed.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.try_borrow().expect("already mutably borrowed")
+    }
+    /// Immutably borrows the underlying value or returns an error if the value is currently mutably borrowed
+    ///
+    /// # Panics
+    /// This function panics if the amount of shared borrows would overflow.
+    pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
+        // Ensure that there is no exclusive borrow
+        let borrow = &self.cell.borrow;
+        if borrow.get() == EXCLUSIVE {
+            return Err(BorrowError);
+        }
+
+        // Register the shared borrow
+        assert!(borrow.get() < EXCLUSIVE - 1, "borrow count overflow");
+        borrow.set(borrow.get() + 1);
+        Ok(Ref { cell: &self.cell })
+    }
+    /// Mutably borrows the underlying value
+    ///
+    /// # Panics
+    /// This function panics if the value is currently borrowed.
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.try_borrow_mut().expect("already borrowed")
+    }
+    /// Mutably borrows the underlying value or returns an error if the value is currently borrowed
+    pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowMutError> {
+        // Ensure that there is no borrow at all
+        let borrow = &self.cell.borrow;
+        if borrow.get() != 0 {
+            return Err(BorrowMutError);
+        }
+
+        // Register the exclusive borrow
+        borrow.set(EXCLUSIVE);
+        Ok(RefMut { cell: &self.cell })
+    }
+    /// A mutable reference to the underlying value if `self` is the only reference (strong or weak) to it
+    ///
+    /// # Note
+    /// Since no other reference can borrow the value in this case, no borrow flag is necessary.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        let cell = self.cell.get_mut()?;
+        Some(cell.value.get_mut())
+    }
+
+    /// A raw pointer to the underlying value
+    pub fn as_ptr(&self) -> *mut T {
+        self.cell.value.get()
+    }
+    /// Whether `self` and `other` reference the same object
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.cell.ptr_eq(&other.cell)
+    }
+
+    /// The amount of strong references to the underlying value
+    pub fn strong(&self) -> usize {
+        self.cell.strong()
+    }
+    /// The amount of weak references to the underlying value
+    pub fn weak(&self) -> usize {
+        self.cell.weak()
+    }
+
+    /// Creates a new strong reference to the heap allocated object or returns an error if the strong reference count
+    /// would overflow
+    pub fn try_clone_ref(&self) -> Result<Self, RefCountOverflow> {
+        let cell = self.cell.try_clone_ref()?;
+        Ok(Self { cell })
+    }
+    /// Creates a weak reference to the heap allocated object
+    ///
+    /// # Panics
+    /// This function panics if the weak reference count would overflow.
+    pub fn downgrade(&self) -> HeapRefMutWeak<T> {
+        self.try_downgrade().expect("reference count overflow")
+    }
+    /// Creates a weak reference to the heap allocated object or returns an error if the weak reference count would
+    /// overflow
+    pub fn try_downgrade(&self) -> Result<HeapRefMutWeak<T>, RefCountOverflow> {
+        let cell = self.cell.try_downgrade()?;
+        Ok(HeapRefMutWeak { cell })
+    }
+
+    /// Returns the underlying element if `self` is the last strong reference, or releases `self` otherwise
+    pub fn into_inner(self) -> Option<T> {
+        self.try_unwrap().ok()
+    }
+    /// Returns the underlying element
+    pub fn try_unwrap(self) -> Result<T, Self> {
+        match self.cell.try_unwrap() {
+            Ok(cell) => Ok(cell.value.into_inner()),
+            Err(cell) => Err(Self { cell }),
+        }
+    }
+}
+impl<T> Debug for HeapRefMut<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.try_borrow() {
+            Ok(value) => Debug::fmt(&*value, f),
+            Err(_) => f.write_str("<borrowed>"),
+        }
+    }
+}
+impl<T> Pointer for HeapRefMut<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Pointer::fmt(&self.as_ptr(), f)
+    }
+}
+impl<T> Clone for HeapRefMut<T> {
+    /// Creates a new strong reference to the heap allocated object
+    ///
+    /// # Panics
+    /// This function panics if the strong reference count would overflow.
+    fn clone(&self) -> Self {
+        self.try_clone_ref().expect("reference count overflow")
+    }
+}
+impl<T> TryClone for HeapRefMut<T> {
+    /// Creates a new strong reference to the heap allocated object
+    ///
+    /// # Errors
+    /// A strong reference count overflow is reported as `AllocError`.
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        self.try_clone_ref().map_err(|_| AllocError)
+    }
+}
+
+/// A weak reference to a reference counted heap object with interior mutability
+pub struct HeapRefMutWeak<T> {
+    /// The underlying weak reference
+    cell: HeapRefWeak<BorrowCell<T>>,
+}
+impl<T> HeapRefMutWeak<T> {
+    /// Creates a new dangling weak reference that cannot be upgraded
+    ///
+    /// # Note
+    /// This function does not allocate any memory.
+    pub const fn new() -> Self {
+        Self { cell: HeapRefWeak::new() }
+    }
+
+    /// Whether `self` and `other` reference the same object, or whether both are dangling
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.cell.ptr_eq(&other.cell)
+    }
+
+    /// The amount of strong references to the underlying value
+    pub fn strong(&self) -> usize {
+        self.cell.strong()
+    }
+    /// The amount of weak references to the underlying value
+    pub fn weak(&self) -> usize {
+        self.cell.weak()
+    }
+
+    /// Creates a new weak reference to the heap allocated object or returns an error if the weak reference count would
+    /// overflow
+    pub fn try_clone_ref(&self) -> Result<Self, RefCountOverflow> {
+        let cell = self.cell.try_clone_ref()?;
+        Ok(Self { cell })
+    }
+    /// Tries to create a strong reference to the heap object
+    ///
+    /// # Panics
+    /// This function panics if the strong reference count would overflow.
+    pub fn upgrade(&self) -> Option<HeapRefMut<T>> {
+        let cell = self.cell.upgrade()?;
+        Some(HeapRefMut { cell })
+    }
+}
+impl<T> Default for HeapRefMutWeak<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T> Clone for HeapRefMutWeak<T> {
+    /// Creates a new weak reference to the heap allocated object
+    ///
+    /// # Panics
+    /// This function panics if the weak reference count would overflow.
+    fn clone(&self) -> Self {
+        self.try_clone_ref().expect("reference count overflow")
+    }
+}
+impl<T> TryClone for HeapRefMutWeak<T> {
+    /// Creates a new weak reference to the heap allocated object
+    ///
+    /// # Errors
+    /// A weak reference count overflow is reported as `AllocError`.
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        self.try_clone_ref().map_err(|_| AllocError)
+    }
+}
+
+/// A shared borrow of the value of a `HeapRefMut`
+pub struct Ref<'a, T> {
+    /// The borrowed cell
+    cell: &'a BorrowCell<T>,
+}
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.cell.value.get() }
+    }
+}
+impl<T> Debug for Ref<'_, T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+impl<T> Display for Ref<'_, T>
+where
+    T: Display,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+impl<T> Drop for Ref<'_, T> {
+    fn drop(&mut self) {
+        // Release the shared borrow
+        let borrow = &self.cell.borrow;
+        borrow.set(borrow.get() - 1);
+    }
+}
+
+/// An exclusive borrow of the value of a `HeapRefMut`
+pub struct RefMut<'a, T> {
+    /// The borrowed cell
+    cell: &'a BorrowCell<T>,
+}
+impl<T> Deref for RefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.cell.value.get() }
+    }
+}
+impl<T> DerefMut for RefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.cell.value.get() }
+    }
+}
+impl<T> Debug for RefMut<'_, T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+impl<T> Display for RefMut<'_, T>
+where
+    T: Display,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+impl<T> Drop for RefMut<'_, T> {
+    fn drop(&mut self) {
+        // Release the exclusive borrow
+        self.cell.borrow.set(0);
+    }
+}
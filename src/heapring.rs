@@ -0,0 +1,194 @@
+//! A `malloc`/`free`-managed fixed-capacity ring buffer
+
+use crate::{error::AllocError, heap::Heap, tryclone::TryClone};
+use core::{
+    fmt::{self, Debug, Formatter},
+    mem::MaybeUninit,
+    ptr, slice,
+};
+
+/// A heap-allocated ring buffer with a capacity that is fixed at construction
+pub struct HeapRing<T> {
+    /// The underlying memory
+    memory: Heap<[MaybeUninit<T>]>,
+    /// The index of the oldest element
+    head: usize,
+    /// The amount of elements
+    len: usize,
+}
+impl<T> HeapRing<T> {
+    /// Creates a new empty ring buffer with capacity for `capacity` elements
+    pub fn new(capacity: usize) -> Result<Self, AllocError> {
+        let memory = Heap::new_uninit_slice(capacity).ok_or(AllocError)?;
+        Ok(Self { memory, head: 0, len: 0 })
+    }
+
+    /// The capacity of the ring buffer
+    pub fn capacity(&self) -> usize {
+        self.memory.len()
+    }
+    /// The amount of elements in the ring buffer
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+    /// Whether the ring buffer is empty
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// Whether the ring buffer is full
+    pub fn is_full(&self) -> bool {
+        self.len == self.capacity()
+    }
+
+    /// Appends `value` to the ring buffer or returns it as error if the ring buffer is full
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        // Ensure that there is some space left
+        if self.is_full() {
+            return Err(value);
+        }
+
+        // Append the element
+        let index = self.physical(self.len);
+        self.memory[index].write(value);
+        self.len += 1;
+        Ok(())
+    }
+    /// Appends `value` to the ring buffer and returns the oldest element if it has been overwritten
+    ///
+    /// # Note
+    /// If the capacity is zero, `value` is returned as-is.
+    pub fn push_overwrite(&mut self, value: T) -> Option<T> {
+        // Remove the oldest element if the ring buffer is full
+        let overwritten = match self.is_full() {
+            true => self.pop(),
+            false => None,
+        };
+
+        // Append the element
+        match self.push(value) {
+            Ok(_) => overwritten,
+            Err(value) => Some(value),
+        }
+    }
+    /// Removes the oldest element from the ring buffer
+    pub fn pop(&mut self) -> Option<T> {
+        // Ensure that there is an element
+        if self.is_empty() {
+            return None;
+        }
+
+        // Take the element
+        let value = unsafe { self.memory[self.head].assume_init_read() };
+        self.head = self.physical(1);
+        self.len -= 1;
+        Some(value)
+    }
+    /// Drops all elements
+    pub fn clear(&mut self) {
+        // Reset the ring buffer first so that the elements are not dropped twice if a drop panics
+        let (front, back) = self.as_mut_slices();
+        let (front, back) = (front as *mut [T], back as *mut [T]);
+        (self.head, self.len) = (0, 0);
+        unsafe { front.drop_in_place() };
+        unsafe { back.drop_in_place() };
+    }
+
+    /// The elements as two contiguous slices, where the first slice contains the oldest elements
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        let ((front, front_len), (back, back_len)) = self.ranges();
+        let memory = self.memory.as_ptr() as *const T;
+        let front = unsafe { slice::from_raw_parts(memory.add(front), front_len) };
+        let back = unsafe { slice::from_raw_parts(memory.add(back), back_len) };
+        (front, back)
+    }
+    /// The elements as two mutable contiguous slices, where the first slice contains the oldest elements
+    pub fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+        let ((front, front_len), (back, back_len)) = self.ranges();
+        let memory = self.memory.as_mut_ptr() as *mut T;
+        let front = unsafe { slice::from_raw_parts_mut(memory.add(front), front_len) };
+        let back = unsafe { slice::from_raw_parts_mut(memory.add(back), back_len) };
+        (front, back)
+    }
+
+    /// Appends as many elements from `values` as fit into the ring buffer and returns the amount of appended elements
+    pub fn write_from(&mut self, values: &[T]) -> usize
+    where
+        T: Copy,
+    {
+        // Copy the elements into the free space, which may wrap around
+        let count = values.len().min(self.capacity() - self.len);
+        let start = self.physical(self.len);
+        let first = count.min(self.capacity() - start);
+        let memory = self.memory.as_mut_ptr() as *mut T;
+        unsafe { ptr::copy_nonoverlapping(values.as_ptr(), memory.add(start), first) };
+        unsafe { ptr::copy_nonoverlapping(values.as_ptr().add(first), memory, count - first) };
+
+        // Update the length
+        self.len += count;
+        count
+    }
+    /// Removes as many of the oldest elements as fit into `target` and returns the amount of removed elements
+    pub fn read_into(&mut self, target: &mut [T]) -> usize
+    where
+        T: Copy,
+    {
+        // Copy the elements from the front and back slices
+        let (front, back) = self.as_slices();
+        let first = target.len().min(front.len());
+        let second = (target.len() - first).min(back.len());
+        target[..first].copy_from_slice(&front[..first]);
+        target[first..first + second].copy_from_slice(&back[..second]);
+
+        // Remove the elements
+        let count = first + second;
+        self.head = self.physical(count);
+        self.len -= count;
+        count
+    }
+
+    /// The physical index of the element at the logical `offset` from the head
+    fn physical(&self, offset: usize) -> usize {
+        let index = self.head + offset;
+        match index >= self.capacity() {
+            true => index - self.capacity(),
+            false => index,
+        }
+    }
+    /// The start indices and lengths of the front and back ranges
+    fn ranges(&self) -> ((usize, usize), (usize, usize)) {
+        let front_len = self.len.min(self.capacity() - self.head);
+        ((self.head, front_len), (0, self.len - front_len))
+    }
+}
+impl<T> Debug for HeapRing<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let (front, back) = self.as_slices();
+        f.debug_list().entries(front).entries(back).finish()
+    }
+}
+impl<T> TryClone for HeapRing<T>
+where
+    T: TryClone,
+{
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        // Allocate the memory with the same capacity
+        let mut clone = Self::new(self.capacity())?;
+
+        // Clone the elements in order; the clone drops the elements that have been cloned so far if an element fails
+        let (front, back) = self.as_slices();
+        for value in front.iter().chain(back) {
+            clone.memory[clone.len].write(value.try_clone()?);
+            clone.len += 1;
+        }
+        Ok(clone)
+    }
+}
+impl<T> Drop for HeapRing<T> {
+    fn drop(&mut self) {
+        // Drop the remaining elements; the memory itself is released by the `Heap`
+        self.clear();
+    }
+}
@@ -0,0 +1,289 @@
+//! A `malloc`/`realloc`/`free`-managed slot map with generational keys
+
+use crate::{error::AllocError, heap::Heap, tryclone::TryClone};
+use core::{
+    fmt::{self, Debug, Formatter},
+    mem::{self, MaybeUninit},
+    ptr, slice,
+};
+
+/// A generational key that identifies a value within a `HeapSlotMap`
+///
+/// # Layout
+/// The lower 16 bits hold the slot index and the upper 16 bits hold the generation of the slot. Since a slot is retired
+/// once its generation is exhausted, a key never matches a value that has been inserted after the key's value has been
+/// removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key(u32);
+impl Key {
+    /// The maximum amount of slots
+    pub const MAX_SLOTS: usize = 1 << 16;
+
+    /// Creates a new key from the given slot index and generation
+    const fn new(index: usize, generation: u16) -> Self {
+        Self(((generation as u32) << 16) | index as u32)
+    }
+    /// Creates a key from its packed representation
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+    /// The packed representation of the key
+    pub const fn to_bits(self) -> u32 {
+        self.0
+    }
+
+    /// The slot index
+    pub const fn index(self) -> usize {
+        (self.0 & 0xFFFF) as usize
+    }
+    /// The generation of the slot
+    pub const fn generation(self) -> u16 {
+        (self.0 >> 16) as u16
+    }
+}
+
+/// The state of a slot
+enum Entry<T> {
+    /// The slot holds a value
+    Occupied(T),
+    /// The slot is free and links to the next free slot if any
+    Vacant(Option<usize>),
+    /// The generation of the slot is exhausted, so the slot is never reused
+    Retired,
+}
+
+/// A slot of the slot map
+struct Slot<T> {
+    /// The current generation of the slot
+    generation: u16,
+    /// The state of the slot
+    entry: Entry<T>,
+}
+
+/// A heap-allocated slot map that refers to its values via generational keys
+///
+/// # Fallible growth
+/// All operations that may grow the slot map are fallible and leave the slot map unchanged if the allocation fails. The
+/// memory is resized via `realloc` and is never shrunk.
+pub struct HeapSlotMap<T> {
+    /// The underlying memory
+    memory: Heap<[MaybeUninit<Slot<T>>]>,
+    /// The amount of initialized slots
+    slots: usize,
+    /// The amount of values
+    len: usize,
+    /// The first free slot if any
+    free: Option<usize>,
+}
+impl<T> HeapSlotMap<T> {
+    /// Creates a new empty slot map
+    pub fn new() -> Result<Self, AllocError> {
+        Self::with_capacity(0)
+    }
+    /// Creates a new empty slot map with capacity for `capacity` values
+    pub fn with_capacity(capacity: usize) -> Result<Self, AllocError> {
+        let memory = Heap::new_uninit_slice(capacity.min(Key::MAX_SLOTS)).ok_or(AllocError)?;
+        Ok(Self { memory, slots: 0, len: 0, free: None })
+    }
+
+    /// The amount of values
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+    /// Whether the slot map is empty
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// The amount of slots that have been allocated
+    pub fn capacity(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// Inserts `value` or returns it as error if the memory cannot be grown
+    pub fn insert(&mut self, value: T) -> Result<Key, T> {
+        match self.reserve_slot() {
+            Ok(index) => Ok(self.occupy(index, value)),
+            Err(_) => Err(value),
+        }
+    }
+    /// Inserts the value that is returned by `f`, which is called with the key of the value
+    ///
+    /// # Note
+    /// This is useful for values that need to know their own key. If the memory cannot be grown, `f` is not called.
+    pub fn try_insert_with<F>(&mut self, f: F) -> Result<Key, AllocError>
+    where
+        F: FnOnce(Key) -> T,
+    {
+        let index = self.reserve_slot()?;
+        let value = f(Key::new(index, self.slot_generation(index)));
+        Ok(self.occupy(index, value))
+    }
+
+    /// Whether `key` refers to a value
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+    /// Gets a reference to the value that `key` refers to
+    pub fn get(&self, key: Key) -> Option<&T> {
+        match self.as_slots().get(key.index()) {
+            Some(Slot { generation, entry: Entry::Occupied(value) }) if *generation == key.generation() => Some(value),
+            _ => None,
+        }
+    }
+    /// Gets a mutable reference to the value that `key` refers to
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        match self.as_mut_slots().get_mut(key.index()) {
+            Some(Slot { generation, entry: Entry::Occupied(value) }) if *generation == key.generation() => Some(value),
+            _ => None,
+        }
+    }
+    /// Removes the value that `key` refers to
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        // Ensure that the key is valid
+        self.get(key)?;
+
+        // Free the slot, or retire it if its generation is exhausted
+        let index = key.index();
+        let free = self.free;
+        let slot = &mut self.as_mut_slots()[index];
+        let entry = match slot.generation.checked_add(1) {
+            Some(generation) => {
+                slot.generation = generation;
+                Entry::Vacant(free)
+            }
+            None => Entry::Retired,
+        };
+        let Entry::Occupied(value) = mem::replace(&mut slot.entry, entry) else {
+            unreachable!("slot is not occupied");
+        };
+
+        // Link the slot if it is free
+        if let Entry::Vacant(_) = self.as_slots()[index].entry {
+            self.free = Some(index);
+        }
+        self.len -= 1;
+        Some(value)
+    }
+    /// Retains only the values for which `f` returns `true`
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Key, &mut T) -> bool,
+    {
+        for index in 0..self.slots {
+            let slot = &mut self.as_mut_slots()[index];
+            let key = Key::new(index, slot.generation);
+            if let Entry::Occupied(value) = &mut slot.entry {
+                if !f(key, value) {
+                    self.remove(key);
+                }
+            }
+        }
+    }
+    /// Removes all values without releasing the memory
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+
+    /// Iterates over all keys and values
+    pub fn iter(&self) -> impl Iterator<Item = (Key, &T)> {
+        self.as_slots().iter().enumerate().filter_map(|(index, slot)| match &slot.entry {
+            Entry::Occupied(value) => Some((Key::new(index, slot.generation), value)),
+            _ => None,
+        })
+    }
+    /// Iterates over all keys and mutable values
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Key, &mut T)> {
+        self.as_mut_slots().iter_mut().enumerate().filter_map(|(index, slot)| match &mut slot.entry {
+            Entry::Occupied(value) => Some((Key::new(index, slot.generation), value)),
+            _ => None,
+        })
+    }
+
+    /// Takes a free slot or initializes a new one and returns its index
+    fn reserve_slot(&mut self) -> Result<usize, AllocError> {
+        // Reuse a free slot if possible
+        if let Some(index) = self.free {
+            return Ok(index);
+        }
+
+        // Grow the memory if necessary
+        if self.slots == Key::MAX_SLOTS {
+            return Err(AllocError);
+        }
+        if self.slots == self.capacity() {
+            // To avoid frequent reallocations, the capacity is at least doubled
+            let capacity = self.capacity().saturating_mul(2).clamp(4, Key::MAX_SLOTS);
+            self.memory.try_realloc(capacity)?;
+        }
+
+        // Initialize a new vacant slot
+        self.memory[self.slots].write(Slot { generation: 0, entry: Entry::Vacant(None) });
+        self.free = Some(self.slots);
+        self.slots += 1;
+        Ok(self.slots - 1)
+    }
+    /// The generation of the slot with the given index
+    fn slot_generation(&self, index: usize) -> u16 {
+        self.as_slots()[index].generation
+    }
+    /// Moves `value` into the reserved free slot with the given index and returns its key
+    fn occupy(&mut self, index: usize, value: T) -> Key {
+        let slot = &mut self.as_mut_slots()[index];
+        let Entry::Vacant(next) = slot.entry else {
+            unreachable!("slot is not free");
+        };
+        slot.entry = Entry::Occupied(value);
+
+        // Unlink the slot
+        let key = Key::new(index, slot.generation);
+        self.free = next;
+        self.len += 1;
+        key
+    }
+
+    /// The initialized slots
+    fn as_slots(&self) -> &[Slot<T>] {
+        unsafe { slice::from_raw_parts(self.memory.as_ptr() as *const Slot<T>, self.slots) }
+    }
+    /// The initialized slots
+    fn as_mut_slots(&mut self) -> &mut [Slot<T>] {
+        unsafe { slice::from_raw_parts_mut(self.memory.as_mut_ptr() as *mut Slot<T>, self.slots) }
+    }
+}
+impl<T> Debug for HeapSlotMap<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+impl<T> TryClone for HeapSlotMap<T>
+where
+    T: TryClone,
+{
+    /// Creates a deep copy of the slot map, where all keys of `self` remain valid for the copy
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        // Allocate the memory with the same capacity
+        let memory = Heap::new_uninit_slice(self.capacity()).ok_or(AllocError)?;
+        let mut clone = Self { memory, slots: 0, len: self.len, free: self.free };
+
+        // Clone the slots in order; the clone drops the slots that have been cloned so far if a value fails
+        for slot in self.as_slots() {
+            let entry = match &slot.entry {
+                Entry::Occupied(value) => Entry::Occupied(value.try_clone()?),
+                Entry::Vacant(next) => Entry::Vacant(*next),
+                Entry::Retired => Entry::Retired,
+            };
+            clone.memory[clone.slots].write(Slot { generation: slot.generation, entry });
+            clone.slots += 1;
+        }
+        Ok(clone)
+    }
+}
+impl<T> Drop for HeapSlotMap<T> {
+    fn drop(&mut self) {
+        // Drop the initialized slots; the memory itself is released by the `Heap`
+        unsafe { ptr::drop_in_place(self.as_mut_slots()) };
+    }
+}
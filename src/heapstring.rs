@@ -0,0 +1,181 @@
+//! A `malloc`/`realloc`/`free`-managed growable string
+
+use crate::{error::AllocError, heap::Heap, heapref::HeapRef, tryclone::TryClone};
+use core::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt::{self, Debug, Display, Formatter, Write},
+    hash::{Hash, Hasher},
+    mem::MaybeUninit,
+    ops::Deref,
+    ptr, str,
+};
+
+/// A growable heap-allocated string
+///
+/// # Fallible growth
+/// All operations that may grow the string are fallible and leave the string unchanged if the allocation fails. This
+/// also applies to `fmt::Write`, which reports allocation failures as `fmt::Error`; i.e. the output of a failed `write!`
+/// may be truncated at the boundary of a formatted fragment.
+pub struct HeapString {
+    /// The underlying memory
+    memory: Heap<[MaybeUninit<u8>]>,
+    /// The length of the string in bytes
+    len: usize,
+}
+impl HeapString {
+    /// Creates a new empty string
+    pub fn new() -> Result<Self, AllocError> {
+        Self::with_capacity(0)
+    }
+    /// Creates a new empty string with capacity for `capacity` bytes
+    pub fn with_capacity(capacity: usize) -> Result<Self, AllocError> {
+        let memory = Heap::new_uninit_slice(capacity).ok_or(AllocError)?;
+        Ok(Self { memory, len: 0 })
+    }
+    /// Creates a new string with a copy of `value`
+    pub fn try_from_str(value: &str) -> Result<Self, AllocError> {
+        let mut this = Self::with_capacity(value.len())?;
+        this.try_push_str(value)?;
+        Ok(this)
+    }
+
+    /// The length of the string in bytes
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+    /// Whether the string is empty
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// The capacity of the string in bytes
+    pub fn capacity(&self) -> usize {
+        self.memory.len()
+    }
+    /// The string as string slice
+    pub fn as_str(&self) -> &str {
+        let bytes = ptr::slice_from_raw_parts(self.memory.as_ptr() as *const u8, self.len);
+        unsafe { str::from_utf8_unchecked(&*bytes) }
+    }
+
+    /// Ensures that the string has capacity for at least `additional` more bytes
+    ///
+    /// # Note
+    /// To avoid frequent reallocations, the capacity is at least doubled if the memory needs to grow.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        // Ensure that the memory needs to grow
+        let required = self.len.checked_add(additional).ok_or(AllocError)?;
+        if required <= self.capacity() {
+            return Ok(());
+        }
+
+        // Grow the memory
+        let capacity = self.capacity().saturating_mul(2).max(required).max(8);
+        self.memory.try_realloc(capacity)
+    }
+    /// Appends `value` to the string
+    pub fn try_push_str(&mut self, value: &str) -> Result<(), AllocError> {
+        // Ensure that there is enough capacity
+        self.try_reserve(value.len())?;
+
+        // Append the bytes
+        let target = unsafe { (self.memory.as_mut_ptr() as *mut u8).add(self.len) };
+        unsafe { ptr::copy_nonoverlapping(value.as_ptr(), target, value.len()) };
+        self.len += value.len();
+        Ok(())
+    }
+    /// Appends `value` to the string
+    pub fn try_push(&mut self, value: char) -> Result<(), AllocError> {
+        self.try_push_str(value.encode_utf8(&mut [0; 4]))
+    }
+    /// Truncates the string to zero length without releasing the memory
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+    /// Releases the excess capacity on a best-effort basis; i.e. the original memory is kept if this fails
+    pub fn shrink_to_fit(&mut self) {
+        self.memory.truncate(self.len);
+    }
+
+    /// Converts the string into a heap string and releases the excess capacity if possible
+    pub fn into_heap(mut self) -> Heap<str> {
+        self.shrink_to_fit();
+        let bytes = unsafe { self.memory.assume_init() };
+        unsafe { Heap::from_raw(bytes.into_raw() as *mut str) }
+    }
+    /// Converts the string into a reference counted heap string
+    ///
+    /// # Note
+    /// Since the string shares the allocation with the reference counters, it is copied into a newly allocated memory
+    /// segment. If this allocation fails, `self` is returned as error.
+    pub fn try_into_heapref(self) -> Result<HeapRef<str>, Self> {
+        HeapRef::new_from_heap_str(self.into_heap()).map_err(Self::from)
+    }
+}
+impl From<Heap<str>> for HeapString {
+    /// Converts the heap string into a growable string without copying
+    fn from(value: Heap<str>) -> Self {
+        let bytes = Heap::<[u8]>::from(value);
+        let len = bytes.len();
+        let memory = unsafe { Heap::from_raw(bytes.into_raw() as *mut [MaybeUninit<u8>]) };
+        Self { memory, len }
+    }
+}
+impl TryClone for HeapString {
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        Self::try_from_str(self.as_str())
+    }
+}
+impl Write for HeapString {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.try_push_str(s).map_err(|_| fmt::Error)
+    }
+}
+impl Deref for HeapString {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+impl AsRef<str> for HeapString {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+impl Borrow<str> for HeapString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+impl PartialEq for HeapString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str().eq(other.as_str())
+    }
+}
+impl Eq for HeapString {}
+impl PartialOrd for HeapString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for HeapString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+impl Hash for HeapString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+impl Debug for HeapString {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+impl Display for HeapString {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
@@ -1,11 +1,38 @@
 #![no_std]
 #![doc = include_str!("../README.md")]
 
+pub mod dmabuffer;
+pub mod error;
 pub mod heap;
+pub mod heaparc;
+pub mod heaparena;
+pub mod heappool;
+pub mod heapqueue;
 pub mod heapref;
+pub mod heaprefmut;
+pub mod heapring;
+pub mod heapslotmap;
+pub mod heapstring;
+pub mod lock;
+mod refcounter;
+pub mod slab;
 pub mod trace;
+pub mod tryclone;
 
 pub use crate::{
+    dmabuffer::DmaBuffer,
+    error::{AllocError, BorrowError, BorrowMutError, RefCountOverflow},
     heap::Heap,
-    heapref::{HeapRef, HeapRefWeak},
+    heaparc::{HeapArc, HeapArcWeak},
+    heaparena::HeapArena,
+    heappool::{HeapPool, PoolBox},
+    heapqueue::HeapQueue,
+    heapref::{HeapRef, HeapRefWeak, UniqueHeapRef},
+    heaprefmut::{HeapRefMut, HeapRefMutWeak},
+    heapring::HeapRing,
+    heapslotmap::HeapSlotMap,
+    heapstring::HeapString,
+    lock::RawLock,
+    slab::SlabAllocator,
+    tryclone::TryClone,
 };
@@ -0,0 +1,66 @@
+//! Pluggable locks for multicore-safe reference counting
+
+/// A global lock that provides a critical section across both cores
+///
+/// # Safety
+/// Implementors must guarantee that at most one closure executes within `critical_section` at any time, regardless of
+/// the core or interrupt handler it is called from, and that all memory accesses within a critical section are visible
+/// to the next critical section.
+pub unsafe trait RawLock {
+    /// Executes `f` within the critical section
+    ///
+    /// # Note
+    /// The closures passed by this crate only update some reference counters; they never allocate or release memory,
+    /// drop values or acquire the lock recursively.
+    fn critical_section<F, R>(f: F) -> R
+    where
+        F: FnOnce() -> R;
+}
+
+/// A lock that uses one of the RP2040's 32 hardware spinlocks and disables the interrupts on the current core while the
+/// lock is held
+///
+/// # Note
+/// The Pico SDK reserves the spinlocks `0..=15` for internal use and uses the spinlocks `16..=23` as shared "striped"
+/// spinlocks. To avoid contention, use a spinlock from `24..=31` that has been claimed via `spin_lock_claim`.
+#[cfg(target_arch = "arm")]
+pub struct HardwareSpinlock<const ID: usize>;
+#[cfg(target_arch = "arm")]
+unsafe impl<const ID: usize> RawLock for HardwareSpinlock<ID> {
+    fn critical_section<F, R>(f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        use core::{
+            arch::asm,
+            hint,
+            sync::atomic::{self, Ordering},
+        };
+
+        /// The address of the first spinlock register within the SIO block
+        const SPINLOCK0: usize = 0xd000_0100;
+        const { assert!(ID < 32, "invalid spinlock ID") };
+        let spinlock = (SPINLOCK0 + 4 * ID) as *mut u32;
+
+        // Save the interrupt state and disable the interrupts
+        let primask: u32;
+        unsafe { asm!("mrs {}, PRIMASK", "cpsid i", out(reg) primask, options(nostack, preserves_flags)) };
+
+        // Acquire the spinlock; reading the register returns zero if the lock is held by someone else
+        while unsafe { spinlock.read_volatile() } == 0 {
+            hint::spin_loop();
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+
+        // Execute the closure and release the spinlock by writing any value to the register
+        let result = f();
+        atomic::compiler_fence(Ordering::SeqCst);
+        unsafe { spinlock.write_volatile(1) };
+
+        // Restore the interrupts if they were enabled before
+        if primask & 1 == 0 {
+            unsafe { asm!("cpsie i", options(nostack, preserves_flags)) };
+        }
+        result
+    }
+}
@@ -0,0 +1,269 @@
+//! The shared reference counter of `HeapRef` and `HeapArc`
+//!
+//! # Memory layout
+//! The reference counter and the value share a single allocation, where the value is stored at a fixed offset after
+//! the reference counter. This allows to convert a reference into a single raw pointer to the value and back.
+
+use crate::{error::RefCountOverflow, heap, trace};
+use core::{ffi::c_void, mem, ptr};
+
+// The reference counter type as selected by the features
+#[cfg(feature = "refcount-u16")]
+type RefCountType = u16;
+#[cfg(all(feature = "refcount-u32", not(feature = "refcount-u16")))]
+type RefCountType = u32;
+#[cfg(not(any(feature = "refcount-u16", feature = "refcount-u32")))]
+type RefCountType = usize;
+
+/// The integer type of the reference counters
+///
+/// # Note
+/// The default type is `usize`. To reduce the memory overhead, narrower counters can be selected via the
+/// `refcount-u16` or `refcount-u32` features. If the counter would overflow, the respective operation panics or
+/// returns an error before the counter is modified.
+pub type RefCount = RefCountType;
+
+/// The memory overhead for the reference counters in bytes
+///
+/// # Note
+/// If the alignment of the value is larger than `OVERHEAD`, the overhead is increased by the necessary padding.
+pub const OVERHEAD: usize = mem::size_of::<RefCounter>();
+
+/// A shared reference counter
+///
+/// # Implicit weak reference
+/// Like with `Rc`, all strong references collectively hold one implicit weak reference. This ensures that the memory
+/// stays alive while the value is dropped, even if the value holds weak references to itself.
+pub(crate) struct RefCounter {
+    /// The amount of strong references
+    pub strong: RefCount,
+    /// The amount of weak references, including the implicit weak reference
+    pub weak: RefCount,
+}
+impl RefCounter {
+    /// The offset of a value with the given alignment within the allocated memory
+    pub const fn offset_for(align: usize) -> usize {
+        OVERHEAD.next_multiple_of(align)
+    }
+    /// The offset of a value of type `T` within the allocated memory
+    pub const fn offset<T>() -> usize {
+        Self::offset_for(mem::align_of::<T>())
+    }
+    /// The alignment of the memory for a reference counter and a value with the given alignment
+    pub const fn align_for(align: usize) -> usize {
+        match align > mem::align_of::<Self>() {
+            true => align,
+            false => mem::align_of::<Self>(),
+        }
+    }
+    /// The amount of memory for a reference counter and a value of type `T`
+    pub const fn size<T>() -> usize {
+        Self::offset::<T>() + mem::size_of::<T>()
+    }
+    /// The amount of memory for a reference counter and the value that `value` points to
+    ///
+    /// # Safety
+    /// The metadata of `value` must be valid; i.e. `value` must have been created from a reference counter that has
+    /// been allocated via `RefCounter::alloc` or `RefCounter::alloc_slice`. The value itself may have been dropped.
+    pub unsafe fn size_of_val<T>(value: *const T) -> usize
+    where
+        T: ?Sized,
+    {
+        Self::offset_for(mem::align_of_val(&*value)) + mem::size_of_val(&*value)
+    }
+
+    /// Allocates the memory for a reference counter and a value of type `T` and initializes the reference counter
+    pub fn alloc<T>(strong: RefCount, weak: RefCount) -> Option<*mut Self> {
+        Self::alloc_raw(Self::size::<T>(), Self::align_for(mem::align_of::<T>()), strong, weak)
+    }
+    /// Allocates the memory for a reference counter and a slice of `len` elements of type `T` and initializes the
+    /// reference counter
+    pub fn alloc_slice<T>(len: usize, strong: RefCount, weak: RefCount) -> Option<*mut Self> {
+        let size = mem::size_of::<T>().checked_mul(len)?.checked_add(Self::offset::<T>())?;
+        Self::alloc_raw(size, Self::align_for(mem::align_of::<T>()), strong, weak)
+    }
+    /// Resizes the memory of a reference counter and a slice of `old_len` elements of type `T` to `len` elements, which
+    /// may move the memory to a new location
+    ///
+    /// # Safety
+    /// `this` must point to a reference counter that has been allocated via `RefCounter::alloc_slice` for `old_len`
+    /// elements of type `T`. If the reallocation fails, the memory is left unchanged.
+    pub unsafe fn realloc_slice<T>(this: *mut Self, old_len: usize, len: usize) -> Option<*mut Self> {
+        // Reallocate the memory
+        let old_size = Self::offset::<T>() + mem::size_of::<T>() * old_len;
+        let size = mem::size_of::<T>().checked_mul(len)?.checked_add(Self::offset::<T>())?;
+        let memory =
+            heap::realloc(this as *mut c_void, old_size, size, Self::align_for(mem::align_of::<T>())) as *mut Self;
+        if memory.is_null() {
+            return None;
+        }
+
+        // Trace the memory
+        trace::decrement_allocated(old_size);
+        trace::increment_allocated(size);
+        Some(memory)
+    }
+    /// Converts the memory of a heap object into the memory of a reference counter and the value by growing it in place
+    /// if possible, and initializes the reference counter
+    ///
+    /// # Safety
+    /// `memory` must have been allocated via `heap::malloc` or `heap::realloc` for a value with `size` bytes and the
+    /// given alignment. If the reallocation fails, `None` is returned and the memory is left unchanged.
+    pub unsafe fn from_heap(
+        memory: *mut c_void,
+        size: usize,
+        align: usize,
+        strong: RefCount,
+        weak: RefCount,
+    ) -> Option<*mut Self> {
+        // Grow the memory
+        let offset = Self::offset_for(align);
+        let new_size = offset.checked_add(size)?;
+        let memory = heap::realloc(memory, size, new_size, Self::align_for(align)) as *mut Self;
+        if memory.is_null() {
+            return None;
+        }
+
+        // Move the value behind the reference counter and initialize the reference counter
+        ptr::copy(memory as *const u8, (memory as *mut u8).add(offset), size);
+        memory.write(Self { strong, weak });
+
+        // Trace the memory
+        trace::decrement_allocated(size);
+        trace::increment_allocated(new_size);
+        Some(memory)
+    }
+    /// Converts the memory of a reference counter and the value back into the memory of a heap object and returns the
+    /// pointer to the moved value
+    ///
+    /// # Note
+    /// The memory of the reference counter is released via `realloc` on a best-effort basis. If this fails, the value
+    /// keeps the original memory, so this never fails.
+    ///
+    /// # Safety
+    /// `this` must point to a reference counter that has been allocated for a value with `size` bytes and the given
+    /// alignment, and there must be no other references to the memory.
+    pub unsafe fn into_heap(this: *mut Self, size: usize, align: usize) -> *mut c_void {
+        // Move the value to the start of the memory
+        let offset = Self::offset_for(align);
+        ptr::copy((this as *const u8).add(offset), this as *mut u8, size);
+
+        // Release the excess memory; never request zero bytes since `realloc` may release the memory in this case
+        let shrunk = heap::realloc(this as *mut c_void, offset + size, size.max(1), align);
+        trace::decrement_allocated(offset + size);
+        trace::increment_allocated(size);
+        match shrunk.is_null() {
+            true => this as *mut c_void,
+            false => shrunk,
+        }
+    }
+    /// Allocates `size` bytes of memory with the given alignment and initializes the reference counter
+    fn alloc_raw(size: usize, align: usize, strong: RefCount, weak: RefCount) -> Option<*mut Self> {
+        // Allocate the memory
+        let memory = heap::malloc(size, align) as *mut Self;
+        if memory.is_null() {
+            return None;
+        }
+
+        // Initialize the reference counter and trace the memory
+        unsafe { memory.write(Self { strong, weak }) };
+        unsafe { trace::increment_allocated(size) };
+        Some(memory)
+    }
+    /// Releases the memory that has been allocated for `value`
+    ///
+    /// # Safety
+    /// This function is unsafe because improper use may lead to memory problems. For example, a double-free may occur if
+    /// the function is called twice on the same pointer.
+    pub unsafe fn free<T>(this: *mut Self, value: *const T)
+    where
+        T: ?Sized,
+    {
+        let size = Self::size_of_val(value);
+        heap::free(this as *mut c_void);
+        trace::decrement_allocated(size);
+    }
+
+    /// Computes the pointer to the value from the pointer to the associated reference counter
+    ///
+    /// # Safety
+    /// `this` must point to a reference counter that has been allocated via `RefCounter::alloc` for a value of type `T`.
+    pub unsafe fn value<T>(this: *mut Self) -> *mut T {
+        let memory = this as *mut u8;
+        memory.add(Self::offset::<T>()) as *mut T
+    }
+    /// Computes the pointer to the slice from the pointer to the associated reference counter
+    ///
+    /// # Safety
+    /// `this` must point to a reference counter that has been allocated via `RefCounter::alloc_slice` for `len` elements
+    /// of type `T`.
+    pub unsafe fn slice<T>(this: *mut Self, len: usize) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(Self::value::<T>(this), len)
+    }
+    /// Computes the pointer to the reference counter from the pointer to the associated value
+    ///
+    /// # Safety
+    /// `value` must point to a value that has been allocated via `RefCounter::alloc` or `RefCounter::alloc_slice`.
+    pub unsafe fn from_value<T>(value: *mut T) -> *mut Self
+    where
+        T: ?Sized,
+    {
+        let memory = value as *mut u8;
+        memory.sub(Self::offset_for(mem::align_of_val(&*value))) as *mut Self
+    }
+    /// Computes the pointer to the reference counter from the pointer to the associated sized value without creating a
+    /// reference to the value, which may have been dropped already
+    ///
+    /// # Safety
+    /// `value` must point to a value that has been allocated via `RefCounter::alloc`.
+    pub unsafe fn from_sized_value<T>(value: *mut T) -> *mut Self {
+        let memory = value as *mut u8;
+        memory.sub(Self::offset::<T>()) as *mut Self
+    }
+
+    /// The amount of strong references
+    #[allow(clippy::unnecessary_cast, reason = "the counter type depends on the enabled features")]
+    pub unsafe fn strong(this: *mut Self) -> usize {
+        (*this).strong as usize
+    }
+    /// The amount of weak references, excluding the implicit weak reference
+    #[allow(clippy::unnecessary_cast, reason = "the counter type depends on the enabled features")]
+    pub unsafe fn weak(this: *mut Self) -> usize {
+        let implicit = ((*this).strong > 0) as usize;
+        (*this).weak as usize - implicit
+    }
+
+    /// Increments the strong reference count if it does not overflow
+    pub unsafe fn increment_strong(this: *mut Self) -> Result<(), RefCountOverflow> {
+        (*this).strong = (*this).strong.checked_add(1).ok_or(RefCountOverflow)?;
+        Ok(())
+    }
+    /// Increments the weak reference count if it does not overflow
+    pub unsafe fn increment_weak(this: *mut Self) -> Result<(), RefCountOverflow> {
+        (*this).weak = (*this).weak.checked_add(1).ok_or(RefCountOverflow)?;
+        Ok(())
+    }
+    /// Decrements the strong reference count and returns whether it was the last strong reference
+    pub unsafe fn decrement_strong(this: *mut Self) -> bool {
+        (*this).strong -= 1;
+        (*this).strong == 0
+    }
+    /// Decrements the weak reference count and returns whether it was the last weak reference
+    pub unsafe fn decrement_weak(this: *mut Self) -> bool {
+        (*this).weak -= 1;
+        (*this).weak == 0
+    }
+    /// Decrements the weak reference count and releases the memory if it was the last weak reference
+    ///
+    /// # Safety
+    /// This function is unsafe because improper use may lead to memory problems. For example, a double-free may occur if
+    /// the function is called for a weak reference that has already been released.
+    pub unsafe fn release_weak<T>(this: *mut Self, value: *const T)
+    where
+        T: ?Sized,
+    {
+        if Self::decrement_weak(this) {
+            Self::free(this, value);
+        }
+    }
+}
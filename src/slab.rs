@@ -0,0 +1,470 @@
+//! A size-class slab allocator that serves small requests from `malloc`-allocated slabs
+//!
+//! # Size classes
+//! Requests of up to 256 bytes are rounded up to the next size class and served from slabs of 1 to 4 KiB, which are
+//! fetched from `malloc` on demand and never returned. Larger requests are forwarded to `malloc`/`realloc`/`free`
+//! as-is. All blocks are aligned to [`ALIGN`] bytes.
+//!
+//! # Backend
+//! If the `slab` feature is enabled, all allocations of this crate are served by a global slab allocator. On the
+//! RP2040, the backend is synchronized via the hardware spinlock 31, which must not be used for anything else; claim it
+//! via `spin_lock_claim(31)` during startup so that the Pico SDK does not hand it out. Allocations with a larger
+//! alignment than [`ALIGN`] bypass the slabs and are served by `posix_memalign` instead.
+
+use crate::{
+    heap::{self, ffi},
+    lock::RawLock,
+};
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    ffi::c_void,
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    mem, ptr, slice,
+};
+
+/// The block sizes of the size classes in bytes
+pub const SIZE_CLASSES: [usize; 6] = [8, 16, 32, 64, 128, 256];
+/// The alignment of all blocks in bytes
+pub const ALIGN: usize = 8;
+
+/// The utilization of a size class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SlabUtilization {
+    /// The block size of the size class in bytes
+    pub size: usize,
+    /// The total amount of blocks within the slabs of the size class
+    pub blocks: usize,
+    /// The amount of blocks in use
+    pub used: usize,
+}
+
+/// A free block that links to the next free block
+struct FreeBlock {
+    /// The next free block if any
+    next: *mut FreeBlock,
+}
+
+/// The free list and the statistics of a size class
+#[derive(Clone, Copy)]
+struct SizeClass {
+    /// The first free block
+    free: *mut FreeBlock,
+    /// The total amount of blocks
+    blocks: usize,
+    /// The amount of blocks in use
+    used: usize,
+}
+
+/// The state of a slab allocator
+///
+/// # Slab directory
+/// All slabs are registered in a directory that is sorted by address, so that the size class of a block can be looked
+/// up via binary search. Since slabs are aligned to [`ALIGN`], each entry stores the slab address with the size class
+/// in the lower bits.
+struct SlabState {
+    /// The size classes
+    classes: [SizeClass; SIZE_CLASSES.len()],
+    /// The slab directory
+    directory: *mut usize,
+    /// The amount of slabs within the directory
+    directory_len: usize,
+    /// The capacity of the directory
+    directory_capacity: usize,
+}
+impl SlabState {
+    /// The mask of the size class within a directory entry
+    const CLASS_MASK: usize = ALIGN - 1;
+
+    /// Creates a new state without any slabs
+    const fn new() -> Self {
+        let class = SizeClass { free: ptr::null_mut(), blocks: 0, used: 0 };
+        let classes = [class; SIZE_CLASSES.len()];
+        Self { classes, directory: ptr::null_mut(), directory_len: 0, directory_capacity: 0 }
+    }
+
+    /// The size class for a request of `size` bytes, or `None` if the request is too large
+    fn class_for(size: usize) -> Option<usize> {
+        SIZE_CLASSES.iter().position(|&class_size| size <= class_size)
+    }
+    /// The size of a slab for the given size class, which holds at least 16 blocks
+    const fn slab_size(class: usize) -> usize {
+        match SIZE_CLASSES[class] {
+            ..=64 => 1024,
+            128 => 2048,
+            _ => 4096,
+        }
+    }
+    /// The amount of blocks within a slab for the given size class
+    const fn slab_blocks(class: usize) -> usize {
+        Self::slab_size(class) / SIZE_CLASSES[class]
+    }
+
+    /// The registered slabs
+    fn directory(&self) -> &[usize] {
+        match self.directory.is_null() {
+            true => &[],
+            false => unsafe { slice::from_raw_parts(self.directory, self.directory_len) },
+        }
+    }
+    /// The size class of the slab that contains `block`, or `None` if the block has not been allocated from a slab
+    fn class_of(&self, block: *mut c_void) -> Option<usize> {
+        // Find the last slab that starts at or before the block
+        let directory = self.directory();
+        let index = directory.partition_point(|&entry| entry & !Self::CLASS_MASK <= block as usize);
+        let entry = *directory.get(index.checked_sub(1)?)?;
+
+        // Ensure that the block is within the slab
+        let (slab, class) = (entry & !Self::CLASS_MASK, entry & Self::CLASS_MASK);
+        (block as usize - slab < Self::slab_size(class)).then_some(class)
+    }
+
+    /// Takes a free block from the given size class, or returns a null pointer if there is no free block
+    fn pop(&mut self, class: usize) -> *mut c_void {
+        let class = &mut self.classes[class];
+        let block = class.free;
+        if !block.is_null() {
+            class.free = unsafe { (*block).next };
+            class.used += 1;
+        }
+        block as *mut c_void
+    }
+    /// Returns a block to the given size class
+    ///
+    /// # Safety
+    /// `block` must have been taken from the given size class and must not be used afterwards.
+    unsafe fn push(&mut self, class: usize, block: *mut c_void) {
+        let class = &mut self.classes[class];
+        let block = block as *mut FreeBlock;
+        block.write(FreeBlock { next: class.free });
+        class.free = block;
+        class.used -= 1;
+    }
+    /// Registers a new slab and links its blocks into the given size class, or returns `false` if the directory is full
+    ///
+    /// # Safety
+    /// `slab` must point to `SlabState::slab_size(class)` bytes of unused memory that have been allocated via `malloc`.
+    unsafe fn push_slab(&mut self, class: usize, slab: *mut c_void) -> bool {
+        // Register the slab
+        if self.directory_len == self.directory_capacity {
+            return false;
+        }
+        let index = self.directory().partition_point(|&entry| entry < slab as usize);
+        let entry = self.directory.add(index);
+        ptr::copy(entry, entry.add(1), self.directory_len - index);
+        entry.write(slab as usize | class);
+        self.directory_len += 1;
+
+        // Link the blocks into the free list
+        let (size, blocks) = (SIZE_CLASSES[class], Self::slab_blocks(class));
+        let class = &mut self.classes[class];
+        for index in (0..blocks).rev() {
+            let block = (slab as *mut u8).add(index * size) as *mut FreeBlock;
+            block.write(FreeBlock { next: class.free });
+            class.free = block;
+        }
+        class.blocks += blocks;
+        true
+    }
+    /// Replaces the directory with a larger one and returns the memory that is not needed anymore
+    ///
+    /// # Safety
+    /// `directory` must point to memory for `capacity` entries that has been allocated via `malloc`.
+    unsafe fn replace_directory(&mut self, directory: *mut usize, capacity: usize) -> *mut usize {
+        // The directory may have been grown concurrently
+        if capacity <= self.directory_capacity {
+            return directory;
+        }
+
+        // Move the entries
+        if !self.directory.is_null() {
+            ptr::copy_nonoverlapping(self.directory, directory, self.directory_len);
+        }
+        self.directory_capacity = capacity;
+        mem::replace(&mut self.directory, directory)
+    }
+
+    /// The utilization of all size classes
+    fn utilization(&self) -> [SlabUtilization; SIZE_CLASSES.len()] {
+        let mut utilization = [SlabUtilization { size: 0, blocks: 0, used: 0 }; SIZE_CLASSES.len()];
+        for (class, entry) in utilization.iter_mut().enumerate() {
+            let SizeClass { blocks, used, .. } = self.classes[class];
+            *entry = SlabUtilization { size: SIZE_CLASSES[class], blocks, used };
+        }
+        utilization
+    }
+    /// Releases all slabs and the directory
+    ///
+    /// # Safety
+    /// All blocks become dangling and must not be used afterwards.
+    unsafe fn release(&mut self) {
+        for &entry in self.directory() {
+            ffi::free((entry & !Self::CLASS_MASK) as *mut c_void);
+        }
+        ffi::free(self.directory as *mut c_void);
+        *self = Self::new();
+    }
+}
+
+/// A size-class slab allocator that uses `L` to synchronize the access to its state
+///
+/// # Global allocator
+/// The allocator implements `GlobalAlloc` and can thus be used as `#[global_allocator]`. In this case, requests with an
+/// alignment larger than [`ALIGN`] bypass the slabs and are served by `posix_memalign`/`free`.
+pub struct SlabAllocator<L>
+where
+    L: RawLock,
+{
+    /// The state
+    state: UnsafeCell<SlabState>,
+    /// The lock
+    lock: PhantomData<L>,
+}
+impl<L> SlabAllocator<L>
+where
+    L: RawLock,
+{
+    /// Creates a new slab allocator without any slabs
+    pub const fn new() -> Self {
+        Self { state: UnsafeCell::new(SlabState::new()), lock: PhantomData }
+    }
+
+    /// Allocates `size` bytes, or returns a null pointer if the allocation fails
+    pub fn alloc(&self, size: usize) -> *mut c_void {
+        // Forward large requests to `malloc`
+        let Some(class) = SlabState::class_for(size) else {
+            return unsafe { ffi::malloc(size) };
+        };
+
+        // Take a free block if possible
+        let block = self.with(|state| state.pop(class));
+        if !block.is_null() {
+            return block;
+        }
+
+        // Fetch a new slab outside of the critical section
+        let slab = unsafe { ffi::malloc(SlabState::slab_size(class)) };
+        if slab.is_null() {
+            return ptr::null_mut();
+        }
+        loop {
+            // Register the slab if the directory has enough capacity
+            let pushed = self.with(|state| match unsafe { state.push_slab(class, slab) } {
+                true => Ok(state.pop(class)),
+                false => Err(state.directory_capacity),
+            });
+            let capacity = match pushed {
+                Ok(block) => return block,
+                Err(capacity) => capacity,
+            };
+
+            // Grow the directory outside of the critical section
+            let capacity = capacity.saturating_mul(2).max(16);
+            let directory = unsafe { ffi::malloc(capacity * mem::size_of::<usize>()) as *mut usize };
+            if directory.is_null() {
+                unsafe { ffi::free(slab) };
+                return ptr::null_mut();
+            }
+            let unused = self.with(|state| unsafe { state.replace_directory(directory, capacity) });
+            unsafe { ffi::free(unused as *mut c_void) };
+        }
+    }
+    /// Resizes a block to `size` bytes, which may move the block to a new location
+    ///
+    /// # Note
+    /// If the block shrinks and cannot be moved into a smaller size class, it is kept as-is. If the resizing fails
+    /// otherwise, a null pointer is returned and the block is left unchanged.
+    ///
+    /// # Safety
+    /// `block` must be null or must have been allocated via `SlabAllocator::alloc` or `SlabAllocator::realloc` of this
+    /// allocator.
+    pub unsafe fn realloc(&self, block: *mut c_void, size: usize) -> *mut c_void {
+        // Forward blocks that do not belong to a slab to `realloc`
+        let Some(class) = self.with(|state| state.class_of(block)) else {
+            return ffi::realloc(block, size);
+        };
+        if SlabState::class_for(size) == Some(class) {
+            return block;
+        }
+
+        // Move the block
+        let moved = self.alloc(size);
+        if moved.is_null() {
+            return match size <= SIZE_CLASSES[class] {
+                true => block,
+                false => ptr::null_mut(),
+            };
+        }
+        ptr::copy_nonoverlapping(block as *const u8, moved as *mut u8, size.min(SIZE_CLASSES[class]));
+        self.with(|state| state.push(class, block));
+        moved
+    }
+    /// Releases a block
+    ///
+    /// # Safety
+    /// `block` must be null or must have been allocated via `SlabAllocator::alloc` or `SlabAllocator::realloc` of this
+    /// allocator.
+    pub unsafe fn free(&self, block: *mut c_void) {
+        // Return the block to its size class if it belongs to a slab
+        let returned = self.with(|state| match state.class_of(block) {
+            Some(class) => {
+                state.push(class, block);
+                true
+            }
+            None => false,
+        });
+
+        // Forward all other blocks to `free`
+        if !returned {
+            ffi::free(block);
+        }
+    }
+
+    /// The utilization of all size classes
+    pub fn utilization(&self) -> [SlabUtilization; SIZE_CLASSES.len()] {
+        self.with(|state| state.utilization())
+    }
+
+    /// Executes `f` with exclusive access to the state
+    ///
+    /// # Note
+    /// To satisfy the contract of `RawLock`, `f` must never allocate or release memory.
+    fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut SlabState) -> R,
+    {
+        L::critical_section(|| f(unsafe { &mut *self.state.get() }))
+    }
+}
+unsafe impl<L> GlobalAlloc for SlabAllocator<L>
+where
+    L: RawLock,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match layout.align() <= ALIGN {
+            true => SlabAllocator::alloc(self, layout.size()) as *mut u8,
+            false => heap::malloc_aligned(layout.size(), layout.align()) as *mut u8,
+        }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // The layout determines the size class, so the directory does not need to be searched
+        match SlabState::class_for(layout.size()) {
+            Some(class) if layout.align() <= ALIGN => self.with(|state| state.push(class, ptr as *mut c_void)),
+            _ => ffi::free(ptr as *mut c_void),
+        }
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // Move overaligned blocks manually, since `realloc` does not preserve the alignment
+        if layout.align() > ALIGN {
+            let moved = heap::malloc_aligned(new_size, layout.align()) as *mut u8;
+            if !moved.is_null() {
+                ptr::copy_nonoverlapping(ptr, moved, layout.size().min(new_size));
+                ffi::free(ptr as *mut c_void);
+            }
+            return moved;
+        }
+
+        // Keep the block if the size class does not change, and forward large blocks to `realloc`
+        let (class, new_class) = (SlabState::class_for(layout.size()), SlabState::class_for(new_size));
+        match (class, new_class) {
+            (Some(class), Some(new_class)) if class == new_class => return ptr,
+            (None, None) => return ffi::realloc(ptr as *mut c_void, new_size) as *mut u8,
+            _ => (),
+        }
+
+        // Move the block
+        let moved = SlabAllocator::alloc(self, new_size) as *mut u8;
+        if !moved.is_null() {
+            ptr::copy_nonoverlapping(ptr, moved, layout.size().min(new_size));
+            GlobalAlloc::dealloc(self, ptr, layout);
+        }
+        moved
+    }
+}
+impl<L> Default for SlabAllocator<L>
+where
+    L: RawLock,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<L> Debug for SlabAllocator<L>
+where
+    L: RawLock,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_list().entries(self.utilization()).finish()
+    }
+}
+impl<L> Drop for SlabAllocator<L>
+where
+    L: RawLock,
+{
+    fn drop(&mut self) {
+        // Release all slabs; any outstanding blocks become dangling
+        unsafe { self.state.get_mut().release() };
+    }
+}
+unsafe impl<L> Send for SlabAllocator<L> where L: RawLock {}
+unsafe impl<L> Sync for SlabAllocator<L> where L: RawLock {}
+
+/// The global slab allocator that serves all allocations of this crate
+#[cfg(feature = "slab")]
+pub(crate) mod backend {
+    use super::SlabAllocator;
+    use core::ffi::c_void;
+
+    /// The lock of the global slab allocator
+    #[cfg(target_arch = "arm")]
+    type BackendLock = crate::lock::HardwareSpinlock<31>;
+
+    /// A spinlock for targets without hardware spinlocks, e.g. for testing on the host
+    #[cfg(not(target_arch = "arm"))]
+    struct BackendLock;
+    #[cfg(not(target_arch = "arm"))]
+    unsafe impl crate::lock::RawLock for BackendLock {
+        fn critical_section<F, R>(f: F) -> R
+        where
+            F: FnOnce() -> R,
+        {
+            use core::{
+                hint,
+                sync::atomic::{AtomicBool, Ordering},
+            };
+
+            /// Whether the lock is held
+            static LOCKED: AtomicBool = AtomicBool::new(false);
+            while LOCKED.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+                hint::spin_loop();
+            }
+
+            // Execute the closure and release the lock
+            let result = f();
+            LOCKED.store(false, Ordering::Release);
+            result
+        }
+    }
+
+    /// The global slab allocator
+    static BACKEND: SlabAllocator<BackendLock> = SlabAllocator::new();
+
+    /// Allocates some memory
+    pub unsafe fn malloc(size: usize) -> *mut c_void {
+        BACKEND.alloc(size)
+    }
+    /// Resizes some allocated memory, which may move the memory to a new location
+    pub unsafe fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+        BACKEND.realloc(ptr, size)
+    }
+    /// Frees some allocated memory
+    pub unsafe fn free(ptr: *mut c_void) {
+        BACKEND.free(ptr)
+    }
+    /// The utilization of all size classes
+    #[cfg(feature = "trace")]
+    pub fn utilization() -> [super::SlabUtilization; super::SIZE_CLASSES.len()] {
+        BACKEND.utilization()
+    }
+}
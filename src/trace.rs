@@ -16,6 +16,15 @@ pub unsafe fn allocated() -> usize {
     ALLOCATED_BYTES
 }
 
+/// The current utilization of the size classes of the slab allocator that serves the allocations of this crate
+///
+/// # Safety
+/// Because the pico does not support atomics, this function __IS NOT__ multicore-safe.
+#[cfg(all(feature = "trace", feature = "slab"))]
+pub unsafe fn slab_utilization() -> [crate::slab::SlabUtilization; crate::slab::SIZE_CLASSES.len()] {
+    crate::slab::backend::utilization()
+}
+
 /// Increases the allocated-bytes counter by `bytes`
 ///
 /// # Safety
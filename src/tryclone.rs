@@ -0,0 +1,103 @@
+//! Fallible cloning
+
+use crate::error::AllocError;
+use core::{
+    mem::{self, MaybeUninit},
+    ptr,
+};
+
+/// A fallible alternative to `Clone` that reports allocation failures instead of panicking
+///
+/// # Note
+/// This trait is implemented explicitly for the primitive types and the types of this crate, so that deep copies of
+/// nested heap objects like `Heap<Heap<[u8; 9]>>` are possible. Reference counted types create a new reference instead
+/// of a deep copy and report a reference count overflow as `AllocError`. Types that only implement `Clone` can be
+/// copied via `Heap::try_clone` instead.
+pub trait TryClone
+where
+    Self: Sized,
+{
+    /// Tries to create a copy of `self`
+    fn try_clone(&self) -> Result<Self, AllocError>;
+    /// Tries to write a copy of `self` into `target`, which is left uninitialized if this fails
+    ///
+    /// # Note
+    /// The default implementation creates the copy on the stack first. Arrays override it to copy their elements one by
+    /// one, so that large arrays can be copied into a heap allocation without exhausting the stack.
+    fn try_clone_to_uninit(&self, target: &mut MaybeUninit<Self>) -> Result<(), AllocError> {
+        target.write(self.try_clone()?);
+        Ok(())
+    }
+}
+
+/// Implements `TryClone` for `Copy` types that can be cloned infallibly
+macro_rules! impl_tryclone_copy {
+    ($($type:ty),* $(,)?) => {
+        $(
+            impl TryClone for $type {
+                fn try_clone(&self) -> Result<Self, AllocError> {
+                    Ok(*self)
+                }
+            }
+        )*
+    };
+}
+impl_tryclone_copy!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char, ());
+
+impl<T> TryClone for &T
+where
+    T: ?Sized,
+{
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        Ok(*self)
+    }
+}
+impl<T> TryClone for Option<T>
+where
+    T: TryClone,
+{
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        match self {
+            Some(value) => Ok(Some(value.try_clone()?)),
+            None => Ok(None),
+        }
+    }
+}
+impl<const LEN: usize, T> TryClone for [T; LEN]
+where
+    T: TryClone,
+{
+    fn try_clone(&self) -> Result<Self, AllocError> {
+        let mut clone = MaybeUninit::uninit();
+        self.try_clone_to_uninit(&mut clone)?;
+        Ok(unsafe { clone.assume_init() })
+    }
+    fn try_clone_to_uninit(&self, target: &mut MaybeUninit<Self>) -> Result<(), AllocError> {
+        // Clone the elements one by one into the target; the guard drops the successful clones if an element fails
+        let memory = target.as_mut_ptr() as *mut MaybeUninit<T>;
+        let mut guard = CloneGuard { memory: memory as *mut T, initialized: 0 };
+        for element in self {
+            let slot = unsafe { &mut *memory.add(guard.initialized) };
+            element.try_clone_to_uninit(slot)?;
+            guard.initialized += 1;
+        }
+
+        // Forget the guard since all elements have been cloned
+        mem::forget(guard);
+        Ok(())
+    }
+}
+
+/// A guard that drops the already cloned elements of a partially cloned array if it is dropped before completion (e.g.
+/// due to an early return or a panic)
+struct CloneGuard<T> {
+    /// The first element
+    memory: *mut T,
+    /// The amount of cloned elements
+    initialized: usize,
+}
+impl<T> Drop for CloneGuard<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::slice_from_raw_parts_mut(self.memory, self.initialized).drop_in_place() };
+    }
+}
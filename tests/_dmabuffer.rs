@@ -0,0 +1,58 @@
+use picosdk_malloc::{trace, DmaBuffer, Heap, TryClone};
+
+pub fn dmabuffer_ring() {
+    // Allocate ring buffers of several sizes
+    for bits in [1, 4, 8, 12, 15] {
+        let size = 1usize << bits;
+        let buffer = DmaBuffer::new(size, 0u8).expect("failed to allocate memory");
+        assert_eq!(unsafe { trace::allocated() }, size, "invalid amount of allocated bytes");
+        assert_eq!(buffer.as_ptr() as usize % size, 0, "misaligned buffer");
+        assert_eq!((buffer.len(), buffer.ring_size_bits()), (size, Some(bits)), "invalid ring size");
+    }
+
+    // Ensure that the ring size respects the element size
+    let mut buffer = DmaBuffer::new(64, 0u32).expect("failed to allocate memory");
+    assert_eq!(buffer.as_ptr() as usize % 256, 0, "misaligned buffer");
+    assert_eq!(buffer.ring_size_bits(), Some(8), "invalid ring size");
+    buffer[63] = 0x7E57_0102;
+    assert_eq!((buffer[0], buffer[63]), (0, 0x7E57_0102), "invalid buffer contents");
+}
+
+pub fn dmabuffer_no_ring() {
+    // Buffers that are not a power of two or too large cannot be used in ring mode
+    let buffer = DmaBuffer::new(100, 7u8).expect("failed to allocate memory");
+    assert_eq!(buffer.as_ptr() as usize % 128, 0, "misaligned buffer");
+    assert_eq!(buffer.ring_size_bits(), None, "invalid ring size");
+    assert!(buffer.iter().all(|&byte| byte == 7), "invalid buffer contents");
+    let buffer = DmaBuffer::new(1 << 16, 0u8).expect("failed to allocate memory");
+    assert_eq!(buffer.ring_size_bits(), None, "invalid ring size");
+    assert_eq!(buffer.align(), DmaBuffer::<u8>::WORD_ALIGN, "invalid buffer alignment");
+
+    // Buffers with a smaller alignment than their size cannot be used in ring mode
+    let buffer = DmaBuffer::new_aligned(64, 0u8, 32).expect("failed to allocate memory");
+    assert_eq!((buffer.as_ptr() as usize % 32, buffer.align()), (0, 32), "misaligned buffer");
+    assert_eq!(buffer.ring_size_bits(), None, "invalid ring size");
+}
+
+pub fn heap_new_aligned() {
+    // Allocate some aligned heap objects
+    for align in [8, 64, 1024, 32768] {
+        let heap = Heap::new_aligned(*b"Testolope", align).expect("failed to allocate memory");
+        assert_eq!(heap.as_ptr() as usize % align, 0, "misaligned heap object");
+        assert_eq!(heap.inner(), b"Testolope", "invalid value on heap");
+    }
+    let slice =
+        Heap::<[std::mem::MaybeUninit<u16>]>::new_uninit_slice_aligned(7, 512).expect("failed to allocate memory");
+    assert_eq!((slice.as_ptr() as usize % 512, slice.len()), (0, 7), "misaligned heap slice");
+}
+
+pub fn dmabuffer_try_clone() {
+    // Clone a ring buffer and ensure that the clone keeps the alignment
+    let mut buffer = DmaBuffer::new(64, 0u8).expect("failed to allocate memory");
+    buffer[7] = 7;
+    let clone = buffer.try_clone().expect("failed to clone buffer");
+    assert_eq!(clone.as_ptr() as usize % 64, 0, "misaligned buffer");
+    assert_eq!((clone.align(), clone.ring_size_bits()), (64, Some(6)), "invalid ring size");
+    assert_eq!((clone[7], &clone[..]), (7, &buffer[..]), "invalid buffer contents");
+    assert_eq!(unsafe { trace::allocated() }, 128, "invalid amount of allocated bytes");
+}
@@ -1,5 +1,17 @@
-use core::{default::Default, mem::MaybeUninit};
-use picosdk_malloc::{trace, Heap};
+use crate::DropCounter;
+use core::{
+    default::Default,
+    fmt::Display,
+    mem::{self, MaybeUninit},
+};
+use picosdk_malloc::{heap_dyn, trace, AllocError, Heap, HeapRef, TryClone};
+use std::{
+    borrow::Borrow,
+    cell::Cell,
+    collections::{hash_map::DefaultHasher, BTreeSet},
+    hash::{Hash, Hasher},
+    panic::{self, AssertUnwindSafe},
+};
 
 pub fn uninit() {
     // Allocate memory
@@ -100,10 +112,457 @@ pub fn new_default() {
 
 pub fn new_from_fn() {
     // Create the init function
-    let mut iterator = b"Testolope".into_iter();
+    let mut iterator = b"Testolope".iter();
     let generator = || *iterator.next().expect("init function is exhausted");
 
     // Allocate memory
     let heap: Heap<[_; 9]> = Heap::new_from_fn(generator).expect("failed to allocate memory");
     assert_eq!(heap.inner(), b"Testolope", "invalid value on heap");
 }
+
+pub fn unsize() {
+    // Allocate memory and convert it into a slice
+    let heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    let heap: Heap<[u8]> = unsafe { heap.unsize(|ptr| ptr as *mut [u8]) };
+    assert_eq!(heap.inner(), b"Testolope", "invalid value on heap");
+    assert_eq!(unsafe { trace::allocated() }, 9, "invalid amount of allocated bytes");
+}
+
+pub fn heap_dyn() {
+    /// A testing trait
+    trait Testolope {
+        fn value(&self) -> &[u8];
+    }
+    impl Testolope for [u8; 9] {
+        fn value(&self) -> &[u8] {
+            self
+        }
+    }
+
+    // Allocate memory and convert it into a trait object
+    let heap: Heap<dyn Testolope> = heap_dyn!(*b"Testolope" => dyn Testolope).ok().expect("failed to allocate memory");
+    assert_eq!(heap.value(), b"Testolope", "invalid value on heap");
+    assert_eq!(unsafe { trace::allocated() }, 9, "invalid amount of allocated bytes");
+}
+
+pub fn heap_dyn_drop() {
+    /// A testing trait
+    trait Testolope {
+        fn value(&self) -> &[u8];
+    }
+    impl Testolope for (DropCounter<'_>, [u8; 9]) {
+        fn value(&self) -> &[u8] {
+            &self.1
+        }
+    }
+
+    // Allocate memory and convert it into a trait object
+    let drops = Cell::new(0);
+    let heap: Heap<dyn Testolope> =
+        heap_dyn!((DropCounter(&drops), *b"Testolope") => dyn Testolope).ok().expect("failed to allocate memory");
+    assert_eq!(heap.value(), b"Testolope", "invalid value on heap");
+    assert_eq!(
+        unsafe { trace::allocated() },
+        mem::size_of::<(DropCounter, [u8; 9])>(),
+        "invalid amount of allocated bytes"
+    );
+
+    // Drop the trait object and ensure that the value and the memory have been released
+    drop(heap);
+    assert_eq!(drops.get(), 1, "invalid amount of drops");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn heap_dyn_fnmut() {
+    // Allocate a closure and convert it into a trait object
+    let calls = Cell::new(0);
+    let mut heap: Heap<dyn FnMut()> =
+        heap_dyn!(|| calls.set(calls.get() + 1) => dyn FnMut()).ok().expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, mem::size_of::<&Cell<usize>>(), "invalid amount of allocated bytes");
+
+    // Call the closure through the trait object
+    (*heap)();
+    heap.inner_mut()();
+    assert_eq!(calls.get(), 2, "invalid amount of calls");
+}
+
+pub fn compare() {
+    // Allocate memory
+    let heap_a = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    let heap_b = Heap::new(*b"Testolopf").expect("failed to allocate memory");
+
+    // Compare the values
+    assert_eq!(heap_a, Heap::new(*b"Testolope").expect("failed to allocate memory"), "invalid comparison result");
+    assert_ne!(heap_a, heap_b, "invalid comparison result");
+    assert!(heap_a < heap_b, "invalid comparison result");
+
+    // Use the heap objects as ordered keys
+    let set = BTreeSet::from([heap_b, heap_a]);
+    assert!(set.contains(b"Testolope"), "invalid borrowed lookup");
+}
+
+pub fn hash() {
+    /// Hashes a value
+    fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Allocate memory and compare the hashes
+    let heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    assert_eq!(hash(&heap), hash(b"Testolope"), "invalid hash");
+}
+
+pub fn display() {
+    // Allocate memory and format it
+    let heap = Heap::new(0x07u8).expect("failed to allocate memory");
+    assert_eq!(heap.to_string(), "7", "invalid display format");
+
+    // Format the pointer
+    let pointer = format!("{:p}", heap.inner() as *const u8);
+    assert_eq!(format!("{heap:p}"), pointer, "invalid pointer format");
+}
+
+pub fn display_dyn() {
+    // Allocate a trait object and format it with the formatting options of the caller
+    let heap: Heap<dyn Display> = heap_dyn!(0x07u8 => dyn Display).expect("failed to allocate memory");
+    assert_eq!(heap.to_string(), "7", "invalid display format");
+    assert_eq!(format!("{heap:>3}"), "  7", "invalid display format");
+}
+
+pub fn borrow() {
+    // Allocate memory and borrow it
+    let heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    let value: &[u8; 9] = heap.borrow();
+    assert_eq!(value, b"Testolope", "invalid value on heap");
+}
+
+pub fn iterator() {
+    // Allocate an iterator
+    let mut heap = Heap::new(b"Testolope".iter()).expect("failed to allocate memory");
+    assert_eq!(heap.len(), 9, "invalid iterator length");
+
+    // Iterate from both ends
+    assert_eq!(heap.next(), Some(&b'T'), "invalid iterator value");
+    assert_eq!(heap.next_back(), Some(&b'e'), "invalid iterator value");
+    assert!(heap.eq(b"estolop"), "invalid iterator values");
+}
+
+pub fn iterator_dyn() {
+    // Allocate an iterator as trait object
+    let mut heap: Heap<dyn Iterator<Item = u8>> =
+        heap_dyn!(b"Testolope".iter().copied() => dyn Iterator<Item = u8>).expect("failed to allocate memory");
+    assert_eq!(heap.size_hint(), (9, Some(9)), "invalid size hint");
+
+    // Iterate through the trait object
+    assert_eq!(heap.next(), Some(b'T'), "invalid iterator value");
+    assert_eq!(heap.nth(1), Some(b's'), "invalid iterator value");
+    assert!(heap.eq(b"tolope".iter().copied()), "invalid iterator values");
+}
+
+pub fn try_clone() {
+    // Allocate memory and clone it
+    let heap =
+        Heap::new(Heap::new(*b"Testolope").expect("failed to allocate memory")).expect("failed to allocate memory");
+    let clone = heap.try_clone().expect("failed to clone heap object");
+    assert_eq!(clone, heap, "invalid cloned value");
+    assert_ne!(clone.inner().inner() as *const _, heap.inner().inner() as *const _, "clone is not a deep copy");
+    assert_eq!(
+        unsafe { trace::allocated() },
+        2 * (9 + Heap::<Heap<[u8; 9]>>::SIZE),
+        "invalid amount of allocated bytes"
+    );
+}
+
+pub fn try_clone_slice() {
+    // Allocate a slice and clone it
+    let heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    let heap: Heap<[u8]> = unsafe { heap.unsize(|ptr| ptr as *mut [u8]) };
+    let clone = heap.try_clone().expect("failed to clone heap object");
+    assert_eq!(clone.inner(), b"Testolope", "invalid cloned value");
+    assert_eq!(unsafe { trace::allocated() }, 18, "invalid amount of allocated bytes");
+}
+
+pub fn try_clone_panic() {
+    /// A testing struct that panics on the third clone and counts how often it has been dropped
+    struct Testolope<'a>(&'a Cell<usize>, DropCounter<'a>);
+    impl TryClone for Testolope<'_> {
+        fn try_clone(&self) -> Result<Self, AllocError> {
+            self.0.set(self.0.get() + 1);
+            assert!(self.0.get() < 3, "clone failed");
+            Ok(Self(self.0, DropCounter(self.1 .0)))
+        }
+    }
+
+    // Allocate a slice
+    let (clones, drops) = (Cell::new(0), Cell::new(0));
+    let heap: Heap<[Testolope; 4]> =
+        Heap::new_from_fn(|| Testolope(&clones, DropCounter(&drops))).expect("failed to allocate memory");
+    let heap: Heap<[Testolope]> = unsafe { heap.unsize(|ptr| ptr as *mut [Testolope]) };
+
+    // Clone the slice and ensure that the already cloned elements and the memory have been released
+    let result = panic::catch_unwind(AssertUnwindSafe(|| heap.try_clone()));
+    assert!(result.is_err(), "no panic when cloning");
+    assert_eq!(drops.get(), 2, "invalid amount of drops");
+    assert_eq!(unsafe { trace::allocated() }, Heap::<[Testolope; 4]>::SIZE, "invalid amount of allocated bytes");
+}
+
+pub fn try_clone_error() {
+    /// A cloneable testing struct whose fallible clone fails on the second attempt and that counts its drops
+    #[derive(Clone)]
+    struct Testolope<'a>(&'a Cell<usize>, DropCounter<'a>);
+    impl TryClone for Testolope<'_> {
+        fn try_clone(&self) -> Result<Self, AllocError> {
+            self.0.set(self.0.get() + 1);
+            match self.0.get() < 2 {
+                true => Ok(Self(self.0, DropCounter(self.1 .0))),
+                false => Err(AllocError),
+            }
+        }
+    }
+
+    // Clone an array and ensure that the custom implementation is used and the successful clones are released
+    let (clones, drops) = (Cell::new(0), Cell::new(0));
+    let heap: Heap<[Testolope; 3]> =
+        Heap::new_from_fn(|| Testolope(&clones, DropCounter(&drops))).expect("failed to allocate memory");
+    // `Heap::try_clone` prefers `Clone`, so the fallible implementation must be called explicitly
+    assert!(TryClone::try_clone(&heap).is_err(), "no error when cloning");
+    assert_eq!((clones.get(), drops.get()), (2, 1), "invalid amount of clones and drops");
+    assert_eq!(unsafe { trace::allocated() }, Heap::<[Testolope; 3]>::SIZE, "invalid amount of allocated bytes");
+}
+
+pub fn try_clone_clone() {
+    /// A testing struct that only implements `Clone`
+    #[derive(Debug, Clone, PartialEq)]
+    struct Testolope(u32, [u8; 9]);
+
+    // Clone a heap object and a heap slice
+    let heap = Heap::new(Testolope(7, *b"Testolope")).expect("failed to allocate memory");
+    let clone = heap.try_clone().expect("failed to clone heap object");
+    assert_eq!(clone, heap, "invalid cloned value");
+    let slice: Heap<[Testolope]> =
+        Heap::<[Testolope; 2]>::new_from_fn(|| Testolope(7, *b"Testolope")).expect("failed to allocate memory").into();
+    let clone = slice.try_clone().expect("failed to clone heap object");
+    assert_eq!(clone, slice, "invalid cloned value");
+    assert_eq!(unsafe { trace::allocated() }, 6 * mem::size_of::<Testolope>(), "invalid amount of allocated bytes");
+}
+
+pub fn try_clone_str() {
+    // Allocate a string and clone it
+    let heap: Heap<str> = Heap::<[u8]>::from(Heap::new(*b"Testolope").expect("failed to allocate memory"))
+        .try_into()
+        .expect("invalid UTF-8");
+    let clone = heap.try_clone().expect("failed to clone heap object");
+    assert_eq!(clone.inner(), "Testolope", "invalid cloned value");
+    assert_ne!(clone.inner().as_ptr(), heap.inner().as_ptr(), "clone is not a deep copy");
+    assert_eq!(unsafe { trace::allocated() }, 18, "invalid amount of allocated bytes");
+}
+
+pub fn try_from_iter() {
+    // Collect an exactly sized iterator
+    let heap = Heap::<[u16]>::try_from_iter((1..=3).map(|value| value * 2)).expect("failed to allocate memory");
+    assert_eq!(heap.inner(), &[2, 4, 6], "invalid value on heap");
+    assert_eq!(unsafe { trace::allocated() }, 6, "invalid amount of allocated bytes");
+    drop(heap);
+
+    // Collect an iterator without a useful size hint, which requires to grow and shrink the memory
+    let heap =
+        Heap::<[u16]>::try_from_iter((0..100).filter(|value| value % 3 == 0)).expect("failed to allocate memory");
+    assert_eq!(heap.len(), 34, "invalid amount of elements");
+    assert!(heap.iter().copied().eq((0..100).step_by(3)), "invalid value on heap");
+    assert_eq!(unsafe { trace::allocated() }, 68, "invalid amount of allocated bytes");
+}
+
+pub fn try_from_iter_panic() {
+    /// A testing struct that counts how often it has been dropped
+    struct Testolope<'a>(&'a Cell<usize>);
+    impl Drop for Testolope<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    // Collect an iterator that panics after some elements have been collected
+    let drops = Cell::new(0);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let iter = (0..10).filter(|_| true).map(|index| match index {
+            7 => panic!("iteration failed"),
+            _ => Testolope(&drops),
+        });
+        Heap::<[Testolope]>::try_from_iter(iter)
+    }));
+
+    // Ensure that the already collected elements and the memory have been released
+    assert!(result.is_err(), "no panic when collecting");
+    assert_eq!(drops.get(), 7, "invalid amount of drops");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn conversions() {
+    // Convert an array into a slice and back
+    let heap = Heap::<[u8; 9]>::new_from_fn(|| b'T').expect("failed to allocate memory");
+    let pointer = heap.as_ptr();
+    let heap = Heap::<[u8]>::from(heap);
+    assert_eq!((heap.as_ptr(), heap.len()), (pointer, 9), "invalid slice");
+    let heap = Heap::<[u8; 4]>::try_from(heap).expect_err("converted slice with invalid length");
+    let heap = Heap::<[u8; 9]>::try_from(heap).expect("failed to convert slice");
+    assert_eq!(heap.as_ptr(), pointer, "slice has been reallocated");
+    assert_eq!(unsafe { trace::allocated() }, 9, "invalid amount of allocated bytes");
+    drop(heap);
+
+    // Convert an element into an array
+    let heap = Heap::new(7u32).expect("failed to allocate memory");
+    let heap = Heap::<[u32; 1]>::from(heap);
+    assert_eq!(heap.inner(), &[7], "invalid value on heap");
+    drop(heap);
+
+    // Convert bytes into a string and back
+    let heap = Heap::<[u8]>::from(Heap::new(*b"Testolope").expect("failed to allocate memory"));
+    let heap = Heap::<str>::try_from(heap).expect("failed to convert valid UTF-8");
+    assert_eq!(heap.inner(), "Testolope", "invalid value on heap");
+    let mut heap = Heap::<[u8]>::from(heap);
+    heap[0] = 0xff;
+    let heap = Heap::<str>::try_from(heap).expect_err("converted invalid UTF-8");
+    assert_eq!(unsafe { trace::allocated() }, 9, "invalid amount of allocated bytes");
+    drop(heap);
+}
+
+pub fn replace_take() {
+    // Replace the element
+    let mut heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    assert_eq!(&heap.replace(*b"Pestolope"), b"Testolope", "invalid old value");
+    assert_eq!(heap.inner(), b"Pestolope", "invalid value on heap");
+
+    // Take the element
+    let mut heap = Heap::new(7u32).expect("failed to allocate memory");
+    assert_eq!(heap.take(), 7, "invalid old value");
+    assert_eq!(*heap.inner(), 0, "invalid value on heap");
+    assert_eq!(unsafe { trace::allocated() }, 13, "invalid amount of allocated bytes");
+}
+
+pub fn map_in_place() {
+    // Map to a type with the same size
+    let heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    let pointer = heap.as_ptr() as usize;
+    let heap = heap.map_in_place(|value| value.map(|byte| byte as i8)).expect("failed to reuse memory");
+    assert_eq!(heap.as_ptr() as usize, pointer, "memory has not been reused");
+    assert_eq!(unsafe { trace::allocated() }, 9, "invalid amount of allocated bytes");
+
+    // Map to a smaller and a larger type
+    let heap = heap.map_in_place(|value| value[0] as u8).expect("failed to shrink memory");
+    assert_eq!((*heap.inner(), unsafe { trace::allocated() }), (b'T', 1), "invalid value on heap");
+    assert_eq!(heap.inner() as *const u8 as usize, pointer, "memory has not been reused");
+    let heap = heap.map_in_place(|value| [value; 16]).expect("failed to grow memory");
+    assert_eq!((heap.inner(), unsafe { trace::allocated() }), (&[b'T'; 16], 16), "invalid value on heap");
+    drop(heap);
+
+    // Release the memory if the mapping panics
+    let heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    let result = panic::catch_unwind(AssertUnwindSafe(|| heap.map_in_place(|_| -> u8 { panic!("mapping failed") })));
+    assert!(result.is_err(), "no panic when mapping");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn map_in_place_aligned() {
+    /// A helper struct with a large alignment
+    #[repr(align(64))]
+    struct Testolope([u8; 9]);
+
+    // Map to a type with a larger alignment
+    for _ in 0..16 {
+        let heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+        let heap = heap.map_in_place(Testolope).expect("failed to allocate aligned memory");
+        assert_eq!(heap.inner() as *const Testolope as usize % 64, 0, "misaligned heap object");
+        assert_eq!(&heap.inner().0, b"Testolope", "invalid value on heap");
+        assert_eq!(unsafe { trace::allocated() }, 64, "invalid amount of allocated bytes");
+    }
+
+    // Reuse the memory for a type whose alignment is satisfied by every allocation
+    let heap = Heap::new([0u8; 8]).expect("failed to allocate memory");
+    let pointer = heap.as_ptr() as usize;
+    let heap = heap.map_in_place(u64::from_ne_bytes).expect("failed to reuse memory");
+    assert_eq!((heap.inner() as *const u64 as usize, *heap.inner()), (pointer, 0), "memory has not been reused");
+    drop(heap);
+
+    // Release the memory if the mapping panics
+    let heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    let result =
+        panic::catch_unwind(AssertUnwindSafe(|| heap.map_in_place(|_| -> Testolope { panic!("mapping failed") })));
+    assert!(result.is_err(), "no panic when mapping");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn alignment() {
+    /// A helper struct with a large alignment
+    #[repr(align(64))]
+    #[derive(Debug)]
+    struct Testolope([u8; 9]);
+
+    // Allocate some over-aligned heap objects
+    for _ in 0..16 {
+        let wide = Heap::new(u128::MAX).expect("failed to allocate memory");
+        assert_eq!(wide.inner() as *const u128 as usize % mem::align_of::<u128>(), 0, "misaligned heap object");
+        let heap = Heap::new(Testolope(*b"Testolope")).expect("failed to allocate memory");
+        assert_eq!(heap.inner() as *const Testolope as usize % 64, 0, "misaligned heap object");
+        let heapref = HeapRef::new(Testolope(*b"Testolope")).expect("failed to allocate memory");
+        assert_eq!(heapref.inner() as *const Testolope as usize % 64, 0, "misaligned heap object");
+        assert_eq!((&heap.inner().0, &heapref.inner().0), (b"Testolope", b"Testolope"), "invalid value on heap");
+    }
+
+    // Ensure that resizing preserves the alignment
+    let mut slice = Heap::<[u128]>::from(Heap::new([7u128; 2]).expect("failed to allocate memory"));
+    slice.try_resize_with(64, || 7).expect("failed to grow memory");
+    assert_eq!(slice.as_ptr() as usize % mem::align_of::<u128>(), 0, "misaligned heap slice");
+    assert!(slice.iter().all(|&value| value == 7), "invalid value on heap");
+}
+
+pub fn try_resize_with() {
+    // Grow the slice
+    let mut heap = Heap::<[u8]>::from(Heap::new(*b"Test").expect("failed to allocate memory"));
+    let mut next = b'o';
+    heap.try_resize_with(9, || {
+        let byte = next;
+        next = if byte == b'o' { b'l' } else { b'o' };
+        byte
+    })
+    .expect("failed to grow memory");
+    assert_eq!(&heap[..], b"Testololo", "invalid value on heap");
+    assert_eq!(unsafe { trace::allocated() }, 9, "invalid amount of allocated bytes");
+
+    // Shrink the slice
+    heap.try_resize_with(4, || unreachable!()).expect("failed to shrink memory");
+    assert_eq!(&heap[..], b"Test", "invalid value on heap");
+    heap.truncate(8);
+    heap.truncate(2);
+    assert_eq!(&heap[..], b"Te", "invalid value on heap");
+    assert_eq!(unsafe { trace::allocated() }, 2, "invalid amount of allocated bytes");
+}
+
+pub fn try_resize_with_panic() {
+    /// A testing struct that counts how often it has been dropped
+    struct Testolope<'a>(&'a Cell<usize>);
+    impl Drop for Testolope<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    // Grow a slice and panic while creating the third new element
+    let (drops, mut created) = (Cell::new(0), 0);
+    let mut heap = Heap::<[Testolope]>::try_from_iter([Testolope(&drops)]).expect("failed to allocate memory");
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        heap.try_resize_with(8, || {
+            created += 1;
+            assert!(created < 3, "creation failed");
+            Testolope(&drops)
+        })
+    }));
+
+    // Ensure that the slice keeps the already created elements
+    assert!(result.is_err(), "no panic when resizing");
+    assert_eq!((heap.len(), drops.get()), (3, 0), "invalid slice after panic");
+    assert_eq!(unsafe { trace::allocated() }, Heap::<[Testolope; 3]>::SIZE, "invalid amount of allocated bytes");
+    drop(heap);
+    assert_eq!(drops.get(), 3, "invalid amount of drops");
+}
@@ -0,0 +1,138 @@
+use crate::StdLock;
+use picosdk_malloc::{heaparc::OVERHEAD, trace, Heap, HeapArc, HeapArcWeak};
+use std::thread;
+
+/// A shared heap object that uses the std lock
+type Arc<T> = HeapArc<T, StdLock>;
+/// A weak reference that uses the std lock
+type Weak<T> = HeapArcWeak<T, StdLock>;
+
+pub fn heaparc_new() {
+    // Allocate memory
+    let heaparc = Arc::new(*b"Testolope").expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+    assert_eq!(heaparc.inner(), b"Testolope", "invalid value on heap");
+    drop(heaparc);
+
+    // Move a heap object into a heaparc
+    let heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    let heaparc = Arc::new_from_heap(heap).expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+    assert_eq!(heaparc.inner(), b"Testolope", "invalid value on heap");
+}
+
+pub fn heaparc_clone_downgrade() {
+    // Allocate memory and create some references
+    let heaparc = Arc::new(*b"Testolope").expect("failed to allocate memory");
+    let clone = heaparc.clone();
+    let weak = heaparc.downgrade();
+    assert_eq!((heaparc.strong(), heaparc.weak()), (2, 1), "invalid reference count");
+    assert!(heaparc.ptr_eq(&clone), "references point to different objects");
+
+    // Upgrade the weak reference while there are strong references left
+    let upgraded = weak.upgrade().expect("failed to upgrade weak reference");
+    assert_eq!((weak.strong(), weak.weak()), (3, 1), "invalid reference count");
+    drop((heaparc, clone, upgraded));
+
+    // The value is gone, but the memory is still alive until the weak reference is dropped
+    assert!(weak.upgrade().is_none(), "upgraded weak reference to dropped value");
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+    drop(weak);
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+
+    // Dangling weak references do not allocate
+    let dangling = Weak::<[u8; 9]>::new();
+    assert!(dangling.upgrade().is_none(), "upgraded dangling weak reference");
+    assert_eq!((dangling.strong(), dangling.weak()), (0, 0), "invalid reference count");
+}
+
+pub fn heaparc_try_unwrap() {
+    // Unwrap a shared reference
+    let heaparc = Arc::new(*b"Testolope").expect("failed to allocate memory");
+    let clone = heaparc.clone();
+    let heaparc = heaparc.try_unwrap().expect_err("unwrapped shared reference");
+    drop(clone);
+
+    // Unwrap the last reference while a weak reference is alive
+    let weak = heaparc.downgrade();
+    let value = heaparc.try_unwrap().expect("failed to unwrap last reference");
+    assert_eq!(&value, b"Testolope", "invalid value");
+    assert!(weak.upgrade().is_none(), "upgraded weak reference to unwrapped value");
+    drop(weak);
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+
+    // Unwrap into a heap object
+    let heaparc = Arc::new(*b"Testolope").expect("failed to allocate memory");
+    let heap = heaparc.try_unwrap_heap().expect("failed to unwrap last reference");
+    assert_eq!(unsafe { trace::allocated() }, 9, "invalid amount of allocated bytes");
+    assert_eq!(heap.inner(), b"Testolope", "invalid value");
+}
+
+pub fn heaparc_try_make_mut() {
+    // Clone-on-write for a shared reference
+    let mut heaparc = Arc::new(*b"Testolope").expect("failed to allocate memory");
+    let clone = heaparc.clone();
+    heaparc.try_make_mut().expect("failed to allocate memory")[0] = b'P';
+    assert_eq!(heaparc.inner(), b"Pestolope", "invalid value on heap");
+    assert_eq!(clone.inner(), b"Testolope", "invalid value on heap");
+    assert!(!heaparc.ptr_eq(&clone), "references point to the same object");
+    drop(clone);
+
+    // Disassociate weak references from the last strong reference
+    let weak = heaparc.downgrade();
+    heaparc.try_make_mut().expect("failed to allocate memory")[0] = b'T';
+    assert!(weak.upgrade().is_none(), "upgraded disassociated weak reference");
+    assert_eq!(heaparc.get_mut().expect("unexpected shared reference"), b"Testolope", "invalid value on heap");
+    drop((heaparc, weak));
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn heaparc_try_new_cyclic() {
+    /// A value that references itself
+    struct Cyclic {
+        this: Weak<Cyclic>,
+    }
+
+    // Create the self-referencing value
+    let heaparc = Arc::try_new_cyclic(|this| Cyclic { this: this.clone() }).expect("failed to allocate memory");
+    let this = heaparc.this.upgrade().expect("failed to upgrade weak reference");
+    assert!(heaparc.ptr_eq(&this), "references point to different objects");
+    drop((heaparc, this));
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn heaparc_into_raw() {
+    // Round-trip strong references
+    let heaparc = Arc::new(*b"Testolope").expect("failed to allocate memory");
+    let raw = heaparc.into_raw();
+    unsafe { Arc::increment_strong_count(raw) };
+    unsafe { Arc::decrement_strong_count(raw) };
+    let heaparc = unsafe { Arc::from_raw(raw) };
+    assert_eq!(heaparc.strong(), 1, "invalid reference count");
+
+    // Round-trip weak references
+    let raw = heaparc.downgrade().into_raw();
+    unsafe { Weak::increment_weak_count(raw) };
+    assert_eq!(heaparc.weak(), 2, "invalid reference count");
+    unsafe { Weak::decrement_weak_count(raw) };
+    drop(unsafe { Weak::from_raw(raw) });
+    assert_eq!(heaparc.weak(), 0, "invalid reference count");
+}
+
+pub fn heaparc_threads() {
+    // Clone and drop the references concurrently; note that the threads must not allocate via the traced allocator
+    let heaparc = Arc::new(*b"Testolope").expect("failed to allocate memory");
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            let heaparc = heaparc.clone();
+            scope.spawn(move || {
+                for _ in 0..10_000 {
+                    let clone = heaparc.clone();
+                    let weak = clone.downgrade();
+                    assert_eq!(weak.upgrade().expect("failed to upgrade weak reference").inner(), b"Testolope");
+                }
+            });
+        }
+    });
+    assert_eq!((heaparc.strong(), heaparc.weak()), (1, 0), "invalid reference count");
+}
@@ -0,0 +1,49 @@
+use picosdk_malloc::{trace, HeapArena};
+
+pub fn heaparena_alloc() {
+    // Allocate some objects
+    let arena = HeapArena::new(32).expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 48, "invalid amount of allocated bytes");
+    let byte = arena.alloc(7u8).expect("failed to allocate object");
+    let word = arena.alloc(0x7E57_0102u32).expect("failed to allocate object");
+    assert_eq!(word as *mut u32 as usize % 4, 0, "misaligned object");
+    let string = arena.alloc_str("Testolope").expect("failed to allocate string");
+    let slice = arena.alloc_slice_copy(&[1u16, 2, 3]).expect("failed to allocate slice");
+    *byte += 1;
+    string.make_ascii_uppercase();
+    assert_eq!((*byte, *word), (8, 0x7E57_0102), "invalid objects");
+    assert_eq!((&*string, &*slice), ("TESTOLOPE", &[1, 2, 3][..]), "invalid objects");
+
+    // Exhaust the arena
+    assert_eq!(arena.remaining(), 8, "invalid amount of remaining bytes");
+    arena.alloc(0u64).expect("failed to allocate object");
+    assert_eq!(arena.alloc(0u8), None, "allocated object in full arena");
+    assert_eq!(unsafe { trace::allocated() }, 48, "invalid amount of allocated bytes");
+}
+
+pub fn heaparena_reset() {
+    // Fill the arena and reset it
+    let mut arena = HeapArena::new(16).expect("failed to allocate memory");
+    arena.alloc_slice_copy(b"Testolope").expect("failed to allocate slice");
+    assert_eq!(arena.alloc_str("Testolope"), None, "allocated string in full arena");
+    arena.reset();
+    assert_eq!(arena.remaining(), 16, "invalid amount of remaining bytes");
+    assert_eq!(arena.alloc_str("Testolope").map(|string| &*string), Some("Testolope"), "invalid string");
+}
+
+pub fn heaparena_growable() {
+    // Chain additional chunks
+    let mut arena = HeapArena::new_growable(16).expect("failed to allocate memory");
+    for value in 0..8u64 {
+        assert_eq!(arena.alloc(value).copied(), Some(value), "invalid object");
+    }
+    assert_eq!(unsafe { trace::allocated() }, 4 * 32, "invalid amount of allocated bytes");
+
+    // Allocate an object that is larger than a chunk
+    arena.alloc([0u8; 64]).expect("failed to allocate object");
+    assert_eq!(unsafe { trace::allocated() }, 4 * 32 + 16 + 64, "invalid amount of allocated bytes");
+
+    // Reset the arena to release all chained chunks
+    arena.reset();
+    assert_eq!(unsafe { trace::allocated() }, 32, "invalid amount of allocated bytes");
+}
@@ -0,0 +1,40 @@
+use crate::DropCounter;
+use picosdk_malloc::{trace, HeapPool};
+use std::cell::Cell;
+
+pub fn heappool_alloc() {
+    // Exhaust the pool
+    let pool = HeapPool::new(2).expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 16 + 2 * 8, "invalid amount of allocated bytes");
+    let mut first = pool.alloc(7u64).expect("failed to allocate slot");
+    let second = pool.alloc(4u64).expect("failed to allocate slot");
+    assert_eq!(pool.available(), 0, "invalid amount of available slots");
+    assert_eq!(pool.alloc(3).map(|_| ()), Err(3), "allocated slot in exhausted pool");
+
+    // Recycle the slots
+    *first += 1;
+    assert_eq!((*first, *second), (8, 4), "invalid values");
+    assert_eq!(second.into_inner(), 4, "invalid value");
+    drop(first);
+    assert_eq!((pool.capacity(), pool.available()), (2, 2), "invalid pool statistics");
+    assert_eq!(*pool.alloc(1).expect("failed to allocate slot"), 1, "invalid value");
+}
+
+pub fn heappool_drop() {
+    // Drop the boxes
+    let drops = Cell::new(0);
+    let pool = HeapPool::new(4).expect("failed to allocate memory");
+    let boxes: Vec<_> =
+        (0..3).map(|_| pool.alloc(DropCounter(&drops)).ok().expect("failed to allocate slot")).collect();
+    drop(boxes);
+    assert_eq!((drops.get(), pool.available()), (3, 4), "invalid drops");
+}
+
+pub fn heappool_growable() {
+    // Grow the pool by additional chunks
+    let pool = HeapPool::new_growable(2).expect("failed to allocate memory");
+    let boxes: Vec<_> = (0..5u32).map(|value| pool.alloc(value).expect("failed to allocate slot")).collect();
+    assert_eq!((pool.capacity(), pool.available()), (6, 1), "invalid pool statistics");
+    assert_eq!(unsafe { trace::allocated() }, 3 * (16 + 2 * 8), "invalid amount of allocated bytes");
+    assert!(boxes.iter().map(|value| **value).eq(0..5), "invalid values");
+}
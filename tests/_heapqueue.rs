@@ -0,0 +1,85 @@
+use crate::DropCounter;
+use picosdk_malloc::{trace, HeapQueue};
+use std::{cell::Cell, mem, thread};
+
+pub fn heapqueue_push_pop() {
+    // Fill the queue
+    let mut queue = HeapQueue::<u32, 4>::new().expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 8 + 4 * mem::size_of::<u32>(), "invalid amount of allocated bytes");
+    let (mut producer, mut consumer) = queue.split();
+    for value in 0..4 {
+        producer.push(value).expect("failed to push element");
+    }
+    assert!(producer.is_full(), "queue is not full");
+    assert_eq!(producer.push(4), Err(4), "pushed element into full queue");
+
+    // Drain the queue
+    for value in 0..4 {
+        assert_eq!(consumer.pop(), Some(value), "invalid popped element");
+    }
+    assert!(consumer.is_empty(), "queue is not empty");
+    assert_eq!(consumer.pop(), None, "popped element from empty queue");
+}
+
+pub fn heapqueue_drop() {
+    // Wrap around and drop the queue with some remaining elements
+    let drops = Cell::new(0);
+    let mut queue = HeapQueue::<DropCounter, 3>::new().expect("failed to allocate memory");
+    let (mut producer, mut consumer) = queue.split();
+    for _ in 0..5 {
+        assert!(producer.push(DropCounter(&drops)).is_ok(), "failed to push element");
+        if producer.is_full() {
+            drop(consumer.pop());
+        }
+    }
+    assert_eq!((queue.len(), drops.get()), (2, 3), "invalid queue state");
+    drop(queue);
+    assert_eq!(drops.get(), 5, "invalid amount of drops");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn heapqueue_threads() {
+    // Exchange some elements between two threads; note that the threads must not allocate via the traced allocator
+    const COUNT: u32 = 100_000;
+    let mut queue = HeapQueue::<u32, 16>::new().expect("failed to allocate memory");
+    let (mut producer, mut consumer) = queue.split();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            for value in 0..COUNT {
+                while producer.push(value).is_err() {
+                    thread::yield_now();
+                }
+            }
+        });
+        scope.spawn(move || {
+            for expected in 0..COUNT {
+                let value = loop {
+                    match consumer.pop() {
+                        Some(value) => break value,
+                        None => thread::yield_now(),
+                    }
+                };
+                assert_eq!(value, expected, "invalid popped element");
+            }
+        });
+    });
+    assert!(queue.is_empty(), "queue is not empty");
+}
+
+pub fn heapqueue_wrap() {
+    // Wrap the indices many times with a capacity that is not a power of two, and partially filled queues
+    let mut queue = HeapQueue::<u64, 3>::new().expect("failed to allocate memory");
+    let (mut producer, mut consumer) = queue.split();
+    let (mut pushed, mut popped) = (0, 0);
+    for round in 0..1000u64 {
+        for _ in 0..=(round % 2) {
+            producer.push(pushed).expect("failed to push element");
+            pushed += 1;
+        }
+        while popped + 1 < pushed {
+            assert_eq!(consumer.pop(), Some(popped), "invalid popped element");
+            popped += 1;
+        }
+    }
+    assert_eq!(queue.len(), 1, "invalid queue length");
+}
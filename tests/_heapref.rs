@@ -1,4 +1,9 @@
-use picosdk_malloc::{heapref::OVERHEAD, trace, Heap, HeapRef};
+use picosdk_malloc::{heapref::OVERHEAD, trace, Heap, HeapRef, HeapRefWeak, TryClone, UniqueHeapRef};
+use std::{
+    cell::Cell,
+    ffi::c_void,
+    panic::{self, AssertUnwindSafe},
+};
 
 pub fn heapref_new_from_heap() {
     // Allocate memory
@@ -62,6 +67,22 @@ pub fn heapref_try_unwrap_heap() {
     drop(value);
 }
 
+pub fn heapref_try_unwrap_heap_weak() {
+    // Allocate memory and downgrade the heapref
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let weak = heapref.downgrade();
+
+    // Unwrap the heap object, which moves the value into a new allocation since the weak reference keeps the memory
+    let value = heapref.try_unwrap_heap().expect("failed to unwrap exclusive heap reference");
+    assert_eq!(value.inner(), b"Testolope", "invalid value on heap");
+    assert_eq!(unsafe { trace::allocated() }, 9 + 9 + OVERHEAD, "invalid amount of allocated bytes");
+    assert!(weak.upgrade().is_none(), "upgraded weak reference to unwrapped value");
+
+    // Drop the weak reference
+    drop(weak);
+    assert_eq!(unsafe { trace::allocated() }, 9, "invalid amount of allocated bytes");
+}
+
 pub fn heapref_try_unwrap() {
     // Allocate memory and clone heapref
     let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
@@ -74,9 +95,18 @@ pub fn heapref_try_unwrap() {
     // Unwrap the heap object
     let value = heapref.try_unwrap().expect("failed to unwrap exclusive heap reference");
     assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+    #[allow(dropping_copy_types, reason = "the value is a plain array")]
     drop(value);
 }
 
+pub fn heapref_try_unwrap_value() {
+    // Allocate memory and unwrap the heap object
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let value = heapref.try_unwrap().expect("failed to unwrap exclusive heap reference");
+    assert_eq!(value, *b"Testolope", "invalid value");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
 pub fn heapref_clone() {
     // Allocate memory
     let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
@@ -137,3 +167,397 @@ pub fn heaprefweak_clone() {
     drop(clone);
     assert_eq!(weak.weak(), 1, "invalid weak reference count");
 }
+
+pub fn heapref_compare() {
+    // Allocate memory
+    let heapref_a = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let heapref_b = HeapRef::new(*b"Testolopf").expect("failed to allocate memory");
+
+    // Compare the values
+    assert_eq!(heapref_a, heapref_a.clone(), "invalid comparison result");
+    assert_ne!(heapref_a, heapref_b, "invalid comparison result");
+    assert!(heapref_a < heapref_b, "invalid comparison result");
+}
+
+pub fn heapref_display() {
+    // Allocate memory and format it
+    let heapref = HeapRef::new(0x07u8).expect("failed to allocate memory");
+    assert_eq!(heapref.to_string(), "7", "invalid display format");
+
+    // Format the pointer
+    let pointer = format!("{:p}", heapref.inner() as *const u8);
+    assert_eq!(format!("{heapref:p}"), pointer, "invalid pointer format");
+}
+
+pub fn heapref_try_clone() {
+    // Allocate memory and clone the reference
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let clone = heapref.try_clone().expect("failed to clone reference");
+    assert_eq!(heapref.strong(), 2, "invalid strong reference count");
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+    drop(clone);
+}
+
+pub fn heapref_get_mut() {
+    // Allocate memory and modify the value
+    let mut heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    heapref.get_mut().expect("failed to get exclusive reference").make_ascii_uppercase();
+    assert_eq!(heapref.inner(), b"TESTOLOPE", "invalid value on heap");
+
+    // Ensure that shared references cannot be modified
+    let weak = heapref.downgrade();
+    assert!(heapref.get_mut().is_none(), "no error when getting shared reference");
+    drop(weak);
+
+    // Ensure that shared references cannot be modified
+    let clone = heapref.clone();
+    assert!(heapref.get_mut().is_none(), "no error when getting shared reference");
+    drop(clone);
+}
+
+pub fn heapref_try_make_mut() {
+    // Allocate memory and clone heapref
+    let mut heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let clone = heapref.clone();
+
+    // Modify the value and ensure that the clone has not been modified
+    heapref.try_make_mut().expect("failed to clone value").make_ascii_uppercase();
+    assert_eq!(heapref.inner(), b"TESTOLOPE", "invalid value on heap");
+    assert_eq!(clone.inner(), b"Testolope", "invalid value on heap");
+    assert_eq!(heapref.strong(), 1, "invalid strong reference count");
+    assert_eq!(clone.strong(), 1, "invalid strong reference count");
+    assert_eq!(unsafe { trace::allocated() }, 2 * (9 + OVERHEAD), "invalid amount of allocated bytes");
+    drop(clone);
+
+    // Modify the value and ensure that the weak reference has been disassociated
+    let weak = heapref.downgrade();
+    heapref.try_make_mut().expect("failed to disassociate weak reference").make_ascii_lowercase();
+    assert_eq!(heapref.inner(), b"testolope", "invalid value on heap");
+    assert_eq!(heapref.weak(), 0, "invalid weak reference count");
+    assert!(weak.upgrade().is_none(), "no error when upgrading disassociated weak reference");
+    drop(weak);
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+}
+
+pub fn heapref_try_new_cyclic() {
+    /// A testing struct with a back-reference to itself
+    struct Testolope {
+        this: HeapRefWeak<Testolope>,
+        value: [u8; 9],
+    }
+
+    // Create the cyclic object
+    let heapref = HeapRef::try_new_cyclic(|this: &HeapRefWeak<Testolope>| {
+        assert!(this.upgrade().is_none(), "no error when upgrading weak reference under construction");
+        Testolope { this: this.clone(), value: *b"Testolope" }
+    });
+    let heapref = heapref.expect("failed to allocate memory");
+    assert_eq!(heapref.strong(), 1, "invalid strong reference count");
+    assert_eq!(heapref.weak(), 1, "invalid weak reference count");
+
+    // Upgrade the back-reference
+    let this = heapref.this.upgrade().expect("failed to upgrade weak reference");
+    assert_eq!(&this.value, b"Testolope", "invalid value on heap");
+}
+
+pub fn heapref_try_new_cyclic_panic() {
+    // Create the cyclic object and panic during construction
+    let result =
+        panic::catch_unwind(AssertUnwindSafe(|| HeapRef::<[u8; 9]>::try_new_cyclic(|_| panic!("construction failed"))));
+    assert!(result.is_err(), "no panic when creating object");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn heaprefweak_new() {
+    // Create a dangling reference
+    let weak: HeapRefWeak<[u8; 9]> = HeapRefWeak::new();
+    assert_eq!(weak.strong(), 0, "invalid strong reference count");
+    assert_eq!(weak.weak(), 0, "invalid weak reference count");
+    assert!(weak.upgrade().is_none(), "no error when upgrading dangling weak reference");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+
+    // Clone the reference
+    let clone = weak.clone();
+    assert_eq!(clone.weak(), 0, "invalid weak reference count");
+    drop(clone);
+
+    // Create a default reference
+    let weak: HeapRefWeak<[u8; 9]> = HeapRefWeak::default();
+    assert!(weak.upgrade().is_none(), "no error when upgrading dangling weak reference");
+}
+
+pub fn heapref_into_raw() {
+    // Allocate memory and convert it into a raw pointer
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let memory = heapref.into_raw();
+    assert_eq!(unsafe { memory.read() }, *b"Testolope", "invalid value on heap");
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+
+    // Pass the pointer as untyped pointer and recreate the reference
+    let memory = memory as *mut c_void;
+    let heapref = unsafe { HeapRef::from_raw(memory as *const [u8; 9]) };
+    assert_eq!(heapref.inner(), b"Testolope", "invalid value on heap");
+    assert_eq!(heapref.as_ptr(), memory as *const [u8; 9], "invalid pointer");
+    assert_eq!(heapref.strong(), 1, "invalid strong reference count");
+}
+
+pub fn heapref_strong_count() {
+    // Allocate memory and convert it into a raw pointer
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let memory = heapref.clone().into_raw();
+    assert_eq!(heapref.strong(), 2, "invalid strong reference count");
+
+    // Modify the strong reference count
+    unsafe { HeapRef::increment_strong_count(memory) };
+    assert_eq!(heapref.strong(), 3, "invalid strong reference count");
+    unsafe { HeapRef::decrement_strong_count(memory) };
+    unsafe { HeapRef::decrement_strong_count(memory) };
+    assert_eq!(heapref.strong(), 1, "invalid strong reference count");
+}
+
+pub fn heaprefweak_into_raw() {
+    // Allocate memory and convert a weak reference into a raw pointer
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let memory = heapref.downgrade().into_raw();
+    assert_eq!(memory, heapref.as_ptr(), "invalid pointer");
+
+    // Modify the weak reference count
+    unsafe { HeapRefWeak::increment_weak_count(memory) };
+    assert_eq!(heapref.weak(), 2, "invalid weak reference count");
+    unsafe { HeapRefWeak::decrement_weak_count(memory) };
+    assert_eq!(heapref.weak(), 1, "invalid weak reference count");
+
+    // Recreate the weak reference and release the value
+    let weak = unsafe { HeapRefWeak::from_raw(memory) };
+    assert_eq!(weak.as_ptr(), memory, "invalid pointer");
+    drop(heapref);
+    assert!(weak.upgrade().is_none(), "no error when upgrading orhpaned weak reference");
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+
+    // Convert a dangling reference into a raw pointer and back
+    let memory = HeapRefWeak::<[u8; 9]>::new().into_raw();
+    let weak = unsafe { HeapRefWeak::from_raw(memory) };
+    assert_eq!(weak.weak(), 0, "invalid weak reference count");
+}
+
+pub fn heapref_ptr_eq() {
+    // Allocate memory
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let other = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+
+    // Compare the references
+    assert!(heapref.ptr_eq(&heapref.clone()), "invalid pointer comparison result");
+    assert!(!heapref.ptr_eq(&other), "invalid pointer comparison result");
+    assert_eq!(heapref.as_ptr(), heapref.clone().as_ptr(), "invalid pointer");
+}
+
+pub fn heaprefweak_ptr_eq() {
+    // Allocate memory
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let other = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+
+    // Compare the references
+    assert!(heapref.downgrade().ptr_eq(&heapref.downgrade()), "invalid pointer comparison result");
+    assert!(!heapref.downgrade().ptr_eq(&other.downgrade()), "invalid pointer comparison result");
+    assert!(HeapRefWeak::<u8>::new().ptr_eq(&HeapRefWeak::new()), "invalid pointer comparison result");
+    assert_eq!(heapref.downgrade().as_ptr(), heapref.as_ptr(), "invalid pointer");
+}
+
+pub fn heapref_into_inner() {
+    // Allocate memory and clone heapref
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let clone = heapref.clone();
+
+    // Take the value from the last strong reference
+    assert!(heapref.into_inner().is_none(), "no error when taking shared value");
+    let value = clone.into_inner().expect("failed to take exclusive value");
+    assert_eq!(value, *b"Testolope", "invalid value");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn heapref_unwrap_or_clone() {
+    // Allocate memory and clone heapref
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let clone = heapref.clone();
+
+    // Take the value
+    assert_eq!(heapref.unwrap_or_clone(), *b"Testolope", "invalid value");
+    assert_eq!(clone.strong(), 1, "invalid strong reference count");
+    assert_eq!(clone.unwrap_or_clone(), *b"Testolope", "invalid value");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn heapref_try_clone_ref() {
+    // Allocate memory and clone the references
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let clone = heapref.try_clone_ref().expect("failed to clone reference");
+    let weak = heapref.try_downgrade().expect("failed to downgrade reference");
+    let weak_clone = weak.try_clone_ref().expect("failed to clone weak reference");
+
+    // Validate refcount
+    assert_eq!(heapref.strong(), 2, "invalid strong reference count");
+    assert_eq!(heapref.weak(), 2, "invalid weak reference count");
+    drop((clone, weak, weak_clone));
+}
+
+#[cfg(feature = "refcount-u16")]
+pub fn heapref_overflow() {
+    use picosdk_malloc::{AllocError, RefCountOverflow};
+
+    // Allocate memory and exhaust the reference counters
+    let heapref = HeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    for _ in 1..u16::MAX {
+        let _ = heapref.clone().into_raw();
+        let _ = heapref.downgrade().into_raw();
+    }
+    assert_eq!(heapref.strong(), u16::MAX as usize, "invalid strong reference count");
+
+    // Ensure that the counters cannot overflow
+    assert_eq!(heapref.try_clone_ref().err(), Some(RefCountOverflow), "no error on overflow");
+    assert_eq!(heapref.try_downgrade().err(), Some(RefCountOverflow), "no error on overflow");
+    assert_eq!(heapref.try_clone().err(), Some(AllocError), "no error on overflow");
+    let result = panic::catch_unwind(AssertUnwindSafe(|| heapref.clone()));
+    assert!(result.is_err(), "no panic on overflow");
+    assert_eq!(heapref.strong(), u16::MAX as usize, "invalid strong reference count");
+
+    // Release the references
+    for _ in 1..u16::MAX {
+        unsafe { HeapRef::decrement_strong_count(heapref.as_ptr()) };
+        unsafe { HeapRefWeak::decrement_weak_count(heapref.as_ptr()) };
+    }
+    assert_eq!(heapref.strong(), 1, "invalid strong reference count");
+    assert_eq!(heapref.weak(), 0, "invalid weak reference count");
+}
+
+pub fn uniqueheapref_new() {
+    // Allocate memory and mutate the value
+    let mut unique = UniqueHeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+    unique[0] = b'P';
+    assert_eq!(unique.inner(), b"Pestolope", "invalid value on heap");
+    assert_eq!(unique.into_inner(), *b"Pestolope", "invalid value");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+
+    // Move a heap object into a unique reference
+    let heap = Heap::new(*b"Testolope").expect("failed to allocate memory");
+    let unique = UniqueHeapRef::new_from_heap(heap).expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+    assert_eq!(unique.inner(), b"Testolope", "invalid value on heap");
+}
+
+pub fn uniqueheapref_into_shared() {
+    // Create weak references that cannot be upgraded yet
+    let mut unique = UniqueHeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let weak = unique.downgrade();
+    assert_eq!(unique.weak(), 1, "invalid reference count");
+    assert!(weak.upgrade().is_none(), "upgraded weak reference to unique object");
+    unique[0] = b'P';
+
+    // Convert the object without reallocation
+    let pointer = unique.inner() as *const [u8; 9];
+    let heapref = unique.into_shared();
+    assert_eq!(heapref.as_ptr(), pointer, "object has been reallocated");
+    assert_eq!((heapref.strong(), heapref.weak()), (1, 1), "invalid reference count");
+
+    // Upgrade the weak reference
+    let upgraded = weak.upgrade().expect("failed to upgrade weak reference");
+    assert_eq!(upgraded.inner(), b"Pestolope", "invalid value on heap");
+    drop((heapref, upgraded));
+    assert!(weak.upgrade().is_none(), "upgraded weak reference to dropped value");
+    drop(weak);
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+
+    // Drop a unique object while weak references are alive
+    let unique = UniqueHeapRef::new(*b"Testolope").expect("failed to allocate memory");
+    let weak = unique.downgrade();
+    drop(unique);
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+    drop(weak);
+}
+
+pub fn heapref_slice() {
+    // Clone a slice
+    let heapref = HeapRef::<[u16]>::new_from_slice(&[1, 2, 3]).expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 6 + OVERHEAD, "invalid amount of allocated bytes");
+    assert_eq!(heapref.inner(), &[1, 2, 3], "invalid value on heap");
+
+    // Share the slice
+    let clone = heapref.clone();
+    let weak = heapref.downgrade();
+    assert_eq!((weak.strong(), weak.weak()), (2, 1), "invalid reference count");
+    drop((heapref, clone));
+    assert!(weak.upgrade().is_none(), "upgraded weak reference to dropped value");
+    assert_eq!(unsafe { trace::allocated() }, 6 + OVERHEAD, "invalid amount of allocated bytes");
+    drop(weak);
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+
+    // Move a heap slice into a heapref
+    let heap = Heap::new([1u16, 2, 3]).expect("failed to allocate memory");
+    let heap = unsafe { heap.unsize(|ptr| ptr as *mut [u16]) };
+    let heapref = HeapRef::new_from_heap_slice(heap).expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 6 + OVERHEAD, "invalid amount of allocated bytes");
+    assert_eq!(heapref.inner(), &[1, 2, 3], "invalid value on heap");
+
+    // Round-trip the raw pointer
+    let raw = heapref.into_raw();
+    let heapref = unsafe { HeapRef::from_raw(raw) };
+    assert_eq!(heapref.inner(), &[1, 2, 3], "invalid value on heap");
+}
+
+pub fn heapref_slice_from_iter() {
+    /// A testing struct that counts how often it has been dropped
+    struct Testolope<'a>(&'a Cell<usize>);
+    impl Drop for Testolope<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    // Collect an iterator
+    let heapref = HeapRef::<[u16]>::new_from_iter((1..=3).map(|value| value * 2)).expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 6 + OVERHEAD, "invalid amount of allocated bytes");
+    assert_eq!(heapref.inner(), &[2, 4, 6], "invalid value on heap");
+    drop(heapref);
+
+    // Drop the collected elements if the iterator panics
+    let dropped = Cell::new(0);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let iter = (0..3).map(|index| match index {
+            2 => panic!("test panic"),
+            _ => Testolope(&dropped),
+        });
+        HeapRef::<[Testolope]>::new_from_iter(iter)
+    }));
+    assert!(result.is_err(), "iterator did not panic");
+    assert_eq!(dropped.get(), 2, "invalid amount of dropped elements");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn heapref_str() {
+    // Copy a string
+    let heapref = HeapRef::<str>::new_from_str("Testolope").expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+    assert_eq!(heapref.inner(), "Testolope", "invalid value on heap");
+    assert_eq!(format!("{heapref}"), "Testolope", "invalid display output");
+
+    // Share the string
+    let weak = heapref.downgrade();
+    let upgraded = weak.upgrade().expect("failed to upgrade weak reference");
+    assert!(heapref.ptr_eq(&upgraded), "references point to different objects");
+}
+
+pub fn heapref_try_from_iter() {
+    // Collect an iterator without a useful size hint, which requires to grow and shrink the memory
+    let heapref =
+        HeapRef::<[u16]>::try_from_iter((0..100).filter(|value| value % 3 == 0)).expect("failed to allocate memory");
+    assert_eq!(heapref.len(), 34, "invalid amount of elements");
+    assert!(heapref.iter().copied().eq((0..100).step_by(3)), "invalid value on heap");
+    assert_eq!(unsafe { trace::allocated() }, 68 + OVERHEAD, "invalid amount of allocated bytes");
+
+    // Share the slice
+    let weak = heapref.downgrade();
+    drop(heapref);
+    assert!(weak.upgrade().is_none(), "upgraded weak reference to dropped value");
+    drop(weak);
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
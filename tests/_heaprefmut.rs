@@ -0,0 +1,74 @@
+use picosdk_malloc::{heapref::OVERHEAD, heaprefmut::RefCount, trace, BorrowError, BorrowMutError, HeapRefMut};
+use std::mem;
+
+pub fn heaprefmut_new() {
+    // Allocate memory
+    let heaprefmut = HeapRefMut::new(*b"Testolope").expect("failed to allocate memory");
+    let expected = (OVERHEAD + mem::size_of::<RefCount>() + 9).next_multiple_of(mem::align_of::<RefCount>());
+    assert_eq!(unsafe { trace::allocated() }, expected, "invalid amount of allocated bytes");
+    assert_eq!(HeapRefMut::<[u8; 9]>::SIZE, expected, "invalid size");
+    assert_eq!(&*heaprefmut.borrow(), b"Testolope", "invalid value on heap");
+}
+
+pub fn heaprefmut_borrow() {
+    // Borrow the value via two shared references
+    let heaprefmut = HeapRefMut::new(*b"Testolope").expect("failed to allocate memory");
+    let clone = heaprefmut.clone();
+    let (borrow0, borrow1) = (heaprefmut.borrow(), clone.borrow());
+    assert_eq!(&*borrow0, &*borrow1, "invalid value on heap");
+    assert_eq!(clone.try_borrow_mut().err(), Some(BorrowMutError), "mutably borrowed shared value");
+    drop((borrow0, borrow1));
+
+    // Mutate the value
+    let mut borrow = clone.borrow_mut();
+    borrow[0] = b'P';
+    assert_eq!(heaprefmut.try_borrow().err(), Some(BorrowError), "borrowed mutably borrowed value");
+    assert_eq!(heaprefmut.try_borrow_mut().err(), Some(BorrowMutError), "mutably borrowed mutably borrowed value");
+    drop(borrow);
+    assert_eq!(&*heaprefmut.borrow(), b"Pestolope", "invalid value on heap");
+}
+
+pub fn heaprefmut_debug() {
+    // Format the value and a mutably borrowed value
+    let heaprefmut = HeapRefMut::new(7u32).expect("failed to allocate memory");
+    assert_eq!(format!("{heaprefmut:?}"), "7", "invalid debug format");
+    let borrow = heaprefmut.borrow_mut();
+    assert_eq!(format!("{heaprefmut:?}"), "<borrowed>", "invalid debug format");
+    drop(borrow);
+}
+
+pub fn heaprefmut_weak() {
+    // Share the object via a weak reference
+    let heaprefmut = HeapRefMut::new(*b"Testolope").expect("failed to allocate memory");
+    let weak = heaprefmut.downgrade();
+    assert_eq!((weak.strong(), weak.weak()), (1, 1), "invalid reference count");
+    weak.upgrade().expect("failed to upgrade weak reference").borrow_mut()[0] = b'P';
+
+    // Unwrap the value
+    let value = heaprefmut.try_unwrap().expect("failed to unwrap last reference");
+    assert_eq!(&value, b"Pestolope", "invalid value");
+    assert!(weak.upgrade().is_none(), "upgraded weak reference to unwrapped value");
+    drop(weak);
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn heaprefmut_get_mut() {
+    // Get an exclusive reference to an unshared value
+    let mut heaprefmut = HeapRefMut::new(*b"Testolope").expect("failed to allocate memory");
+    heaprefmut.get_mut().expect("failed to get exclusive reference")[0] = b'P';
+    assert_eq!(&*heaprefmut.borrow(), b"Pestolope", "invalid value on heap");
+
+    // Ensure that a clone cannot write while the value is borrowed via another reference
+    let mut clone = heaprefmut.clone();
+    let borrow = heaprefmut.borrow();
+    assert!(clone.get_mut().is_none(), "got exclusive reference to shared value");
+    assert_eq!(&*borrow, b"Pestolope", "invalid value on heap");
+    drop(borrow);
+    drop(heaprefmut);
+
+    // Ensure that weak references prevent exclusive references
+    let weak = clone.downgrade();
+    assert!(clone.get_mut().is_none(), "got exclusive reference to weakly shared value");
+    drop(weak);
+    assert!(clone.get_mut().is_some(), "failed to get exclusive reference");
+}
@@ -0,0 +1,67 @@
+use crate::DropCounter;
+use picosdk_malloc::{trace, HeapRing, TryClone};
+use std::cell::Cell;
+
+pub fn heapring_push_pop() {
+    // Fill the ring buffer
+    let mut ring = HeapRing::new(4).expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::allocated() }, 16, "invalid amount of allocated bytes");
+    for value in 0..4u32 {
+        ring.push(value).expect("failed to push element");
+    }
+    assert_eq!(ring.push(4), Err(4), "pushed element into full ring buffer");
+
+    // Wrap around
+    assert_eq!((ring.pop(), ring.pop()), (Some(0), Some(1)), "invalid popped elements");
+    ring.push(4).expect("failed to push element");
+    assert_eq!(ring.push_overwrite(5), None, "overwrote element in non-full ring buffer");
+    assert_eq!(ring.push_overwrite(6), Some(2), "invalid overwritten element");
+    assert_eq!(ring.as_slices(), (&[3][..], &[4, 5, 6][..]), "invalid slices");
+    assert_eq!(format!("{ring:?}"), "[3, 4, 5, 6]", "invalid debug output");
+}
+
+pub fn heapring_batch() {
+    // Write a batch that wraps around
+    let mut ring = HeapRing::new(8).expect("failed to allocate memory");
+    assert_eq!(ring.write_from(b"Testo"), 5, "invalid amount of written elements");
+    let mut buffer = [0; 4];
+    assert_eq!(ring.read_into(&mut buffer), 4, "invalid amount of read elements");
+    assert_eq!(&buffer, b"Test", "invalid read elements");
+    assert_eq!(ring.write_from(b"lopeTestolope"), 7, "invalid amount of written elements");
+    assert!(ring.is_full(), "ring buffer is not full");
+    assert_eq!(ring.as_slices(), (&b"olop"[..], &b"eTes"[..]), "invalid slices");
+
+    // Read the remaining elements
+    let mut buffer = [0; 16];
+    assert_eq!(ring.read_into(&mut buffer), 8, "invalid amount of read elements");
+    assert_eq!(&buffer[..8], b"olopeTes", "invalid read elements");
+    assert!(ring.is_empty(), "ring buffer is not empty");
+}
+
+pub fn heapring_drop() {
+    // Drop a wrapped-around ring buffer with some remaining elements
+    let drops = Cell::new(0);
+    let mut ring = HeapRing::new(3).expect("failed to allocate memory");
+    for _ in 0..5 {
+        drop(ring.push_overwrite(DropCounter(&drops)));
+    }
+    assert_eq!(drops.get(), 2, "invalid amount of drops");
+    drop(ring);
+    assert_eq!(drops.get(), 5, "invalid amount of drops");
+    assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");
+}
+
+pub fn heapring_try_clone() {
+    // Fill a ring buffer so that it wraps around
+    let mut ring = HeapRing::new(4).expect("failed to allocate memory");
+    ring.write_from(b"Test");
+    ring.read_into(&mut [0; 2]);
+    ring.write_from(b"ol");
+
+    // Clone the ring buffer and ensure that the elements keep their order
+    let mut clone = ring.try_clone().expect("failed to clone ring buffer");
+    assert_eq!((clone.capacity(), clone.len()), (4, 4), "invalid ring buffer statistics");
+    assert_eq!(format!("{clone:?}"), format!("{ring:?}"), "invalid cloned elements");
+    assert_eq!((clone.pop(), ring.pop()), (Some(b's'), Some(b's')), "invalid popped elements");
+    assert_eq!(unsafe { trace::allocated() }, 8, "invalid amount of allocated bytes");
+}
@@ -0,0 +1,84 @@
+use crate::DropCounter;
+use picosdk_malloc::{heapslotmap::Key, HeapSlotMap, TryClone};
+use std::cell::Cell;
+
+pub fn heapslotmap_insert_remove() {
+    // Insert some values
+    let mut map = HeapSlotMap::new().expect("failed to allocate memory");
+    let keys: Vec<Key> = (0..5u32).map(|value| map.insert(value).expect("failed to insert value")).collect();
+    assert_eq!((map.len(), map.capacity()), (5, 8), "invalid slot map statistics");
+    *map.get_mut(keys[1]).expect("missing value") += 10;
+    assert_eq!(map.get(keys[1]), Some(&11), "invalid value");
+
+    // Remove a value and reuse its slot with a new generation
+    assert_eq!(map.remove(keys[2]), Some(2), "invalid removed value");
+    assert_eq!(map.remove(keys[2]), None, "removed value twice");
+    let key = map.insert(7).expect("failed to insert value");
+    assert_eq!((key.index(), key.generation()), (2, 1), "invalid key");
+    assert_eq!((map.get(keys[2]), map.get(key)), (None, Some(&7)), "stale key refers to value");
+    assert_eq!(Key::from_bits(key.to_bits()), key, "invalid key bits");
+
+    // Insert a value that knows its own key
+    let key = map.try_insert_with(|key| key.to_bits()).expect("failed to insert value");
+    assert_eq!(map.get(key), Some(&key.to_bits()), "invalid value");
+}
+
+pub fn heapslotmap_retain() {
+    // Retain the even values
+    let mut map = HeapSlotMap::with_capacity(4).expect("failed to allocate memory");
+    let keys: Vec<Key> = (0..6u32).map(|value| map.insert(value).expect("failed to insert value")).collect();
+    map.retain(|_, value| *value % 2 == 0);
+    assert_eq!(map.iter().map(|(_, value)| *value).collect::<Vec<_>>(), [0, 2, 4], "invalid values");
+    assert_eq!(map.iter().map(|(key, _)| key).collect::<Vec<_>>(), [keys[0], keys[2], keys[4]], "invalid keys");
+    map.iter_mut().for_each(|(_, value)| *value += 1);
+    assert_eq!(format!("{:?}", map.get(keys[4])), "Some(5)", "invalid value");
+
+    // Clear the slot map
+    map.clear();
+    assert!(map.is_empty(), "slot map is not empty");
+    assert!(keys.iter().all(|key| !map.contains_key(*key)), "stale key refers to value");
+}
+
+pub fn heapslotmap_retire() {
+    // Exhaust the generations of a slot
+    let mut map = HeapSlotMap::new().expect("failed to allocate memory");
+    for _ in 0..=u16::MAX {
+        let key = map.insert(()).expect("failed to insert value");
+        assert_eq!(key.index(), 0, "invalid slot index");
+        map.remove(key).expect("missing value");
+    }
+
+    // Ensure that the slot is never reused
+    let key = map.insert(()).expect("failed to insert value");
+    assert_eq!((key.index(), key.generation()), (1, 0), "retired slot has been reused");
+}
+
+pub fn heapslotmap_drop() {
+    // Drop the remaining values
+    let drops = Cell::new(0);
+    let mut map = HeapSlotMap::new().expect("failed to allocate memory");
+    let keys: Vec<Key> =
+        (0..4).map(|_| map.insert(DropCounter(&drops)).ok().expect("failed to insert value")).collect();
+    drop(map.remove(keys[0]));
+    assert_eq!(drops.get(), 1, "invalid drops");
+    drop(map);
+    assert_eq!(drops.get(), 4, "invalid drops");
+}
+
+pub fn heapslotmap_try_clone() {
+    // Insert some values and free a slot
+    let mut map = HeapSlotMap::new().expect("failed to allocate memory");
+    let keys: Vec<Key> = (0..3u32).map(|value| map.insert(value).expect("failed to insert value")).collect();
+    map.remove(keys[1]);
+
+    // Clone the slot map and ensure that the keys remain valid
+    let mut clone = map.try_clone().expect("failed to clone slot map");
+    assert_eq!((clone.len(), clone.capacity()), (2, 4), "invalid slot map statistics");
+    assert_eq!(clone.iter().collect::<Vec<_>>(), map.iter().collect::<Vec<_>>(), "invalid cloned values");
+    assert_eq!(clone.get(keys[1]), None, "stale key refers to value");
+
+    // Ensure that the clone reuses the free slot like the original
+    let key = clone.insert(7).expect("failed to insert value");
+    assert_eq!(key, map.insert(7).expect("failed to insert value"), "invalid key");
+    assert_eq!((key.index(), key.generation()), (1, 1), "invalid key");
+}
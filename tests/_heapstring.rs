@@ -0,0 +1,44 @@
+use core::fmt::Write;
+use picosdk_malloc::{heapref::OVERHEAD, trace, Heap, HeapString, TryClone};
+
+pub fn heapstring_push() {
+    // Append some strings
+    let mut string = HeapString::new().expect("failed to allocate memory");
+    string.try_push_str("Testo").expect("failed to allocate memory");
+    string.try_push('l').expect("failed to allocate memory");
+    string.try_push_str("ope").expect("failed to allocate memory");
+    assert_eq!(&*string, "Testolope", "invalid string");
+    assert_eq!(unsafe { trace::allocated() }, string.capacity(), "invalid amount of allocated bytes");
+
+    // Release the excess capacity
+    string.shrink_to_fit();
+    assert_eq!(unsafe { trace::allocated() }, 9, "invalid amount of allocated bytes");
+    string.clear();
+    assert!(string.is_empty(), "string is not empty");
+}
+
+pub fn heapstring_write() {
+    // Format into the string
+    let mut string = HeapString::with_capacity(4).expect("failed to allocate memory");
+    let name = "Testolope";
+    write!(string, "{name}-{:04}-{:?}", 7, 'x').expect("failed to format string");
+    assert_eq!(string.as_str(), "Testolope-0007-'x'", "invalid string");
+
+    // Clone the string
+    let clone = string.try_clone().expect("failed to allocate memory");
+    assert_eq!(clone, string, "invalid clone");
+}
+
+pub fn heapstring_convert() {
+    // Convert the string into a heap string and back
+    let string = HeapString::try_from_str("Testolope").expect("failed to allocate memory");
+    let heap: Heap<str> = string.into_heap();
+    assert_eq!(heap.inner(), "Testolope", "invalid heap string");
+    let string = HeapString::from(heap);
+    assert_eq!(string.len(), 9, "invalid string length");
+
+    // Convert the string into a reference counted string
+    let heapref = string.try_into_heapref().expect("failed to allocate memory");
+    assert_eq!(heapref.inner(), "Testolope", "invalid reference counted string");
+    assert_eq!(unsafe { trace::allocated() }, 9 + OVERHEAD, "invalid amount of allocated bytes");
+}
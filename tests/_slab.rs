@@ -0,0 +1,123 @@
+use crate::StdLock;
+use picosdk_malloc::{slab::SlabUtilization, SlabAllocator};
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    ptr, slice, thread,
+};
+
+/// A slab allocator that uses the std lock
+type Slab = SlabAllocator<StdLock>;
+
+pub fn slab_alloc() {
+    // Allocate some blocks
+    let slab = Slab::new();
+    let small = slab.alloc(5);
+    let medium = slab.alloc(100);
+    let large = slab.alloc(1000);
+    assert!(!small.is_null() && !medium.is_null() && !large.is_null(), "failed to allocate memory");
+    assert_eq!(small as usize % 8, 0, "misaligned block");
+    assert_eq!(slab.utilization()[0], SlabUtilization { size: 8, blocks: 128, used: 1 }, "invalid utilization");
+    assert_eq!(slab.utilization()[4], SlabUtilization { size: 128, blocks: 16, used: 1 }, "invalid utilization");
+
+    // Recycle a block
+    unsafe { slab.free(small) };
+    assert_eq!(slab.alloc(8), small, "block has not been recycled");
+    unsafe { slab.free(small) };
+    unsafe { slab.free(medium) };
+    unsafe { slab.free(large) };
+    assert!(slab.utilization().iter().all(|class| class.used == 0), "invalid utilization");
+}
+
+pub fn slab_realloc() {
+    // Resize a block within its size class
+    let slab = Slab::new();
+    let block = slab.alloc(9) as *mut u8;
+    unsafe { ptr::copy_nonoverlapping(b"Testolope".as_ptr(), block, 9) };
+    let block = unsafe { slab.realloc(block.cast(), 16) } as *mut u8;
+    assert_eq!(slab.utilization()[1].used, 1, "invalid utilization");
+
+    // Move the block into a larger size class and finally to `malloc`
+    let block = unsafe { slab.realloc(block.cast(), 60) } as *mut u8;
+    assert_eq!((slab.utilization()[1].used, slab.utilization()[3].used), (0, 1), "invalid utilization");
+    let block = unsafe { slab.realloc(block.cast(), 4096) } as *mut u8;
+    assert_eq!(slab.utilization()[3].used, 0, "invalid utilization");
+    assert_eq!(unsafe { slice::from_raw_parts(block, 9) }, b"Testolope", "invalid block contents");
+    unsafe { slab.free(block.cast()) };
+}
+
+pub fn slab_directory() {
+    // Allocate enough blocks to grow the slab directory several times
+    let slab = Slab::new();
+    let blocks: Vec<_> = (0..1024).map(|index| slab.alloc(8 << (index % 6))).collect();
+    assert!(blocks.iter().all(|block| !block.is_null()), "failed to allocate memory");
+    assert!(
+        slab.utilization().iter().all(|class| class.used == 1024 / 6 || class.used == 1024 / 6 + 1),
+        "invalid utilization"
+    );
+
+    // Release the blocks in a different order
+    for block in blocks.iter().rev().step_by(2).chain(blocks.iter().step_by(2)) {
+        unsafe { slab.free(*block) };
+    }
+    assert!(slab.utilization().iter().all(|class| class.used == 0), "invalid utilization");
+}
+
+pub fn slab_threads() {
+    // Allocate and release blocks concurrently
+    let slab = Slab::new();
+    thread::scope(|scope| {
+        for seed in 0..4 {
+            let slab = &slab;
+            scope.spawn(move || {
+                for round in 0..1000 {
+                    let size = 8 << ((seed + round) % 6);
+                    let block = slab.alloc(size) as *mut u8;
+                    assert!(!block.is_null(), "failed to allocate memory");
+                    unsafe { block.write_bytes(seed as u8, size) };
+                    assert!(
+                        unsafe { slice::from_raw_parts(block, size) }.iter().all(|&byte| byte == seed as u8),
+                        "invalid block contents"
+                    );
+                    unsafe { slab.free(block.cast()) };
+                }
+            });
+        }
+    });
+    assert!(slab.utilization().iter().all(|class| class.used == 0), "invalid utilization");
+}
+
+pub fn slab_global_alloc() {
+    // Allocate and resize a block via the global allocator interface
+    let slab = Slab::new();
+    let layout = Layout::from_size_align(24, 8).expect("invalid layout");
+    let block = unsafe { GlobalAlloc::alloc(&slab, layout) };
+    assert!(!block.is_null(), "failed to allocate memory");
+    assert_eq!(slab.utilization()[2].used, 1, "invalid utilization");
+    let block = unsafe { GlobalAlloc::realloc(&slab, block, layout, 200) };
+    assert_eq!((slab.utilization()[2].used, slab.utilization()[5].used), (0, 1), "invalid utilization");
+    unsafe { GlobalAlloc::dealloc(&slab, block, Layout::from_size_align(200, 8).expect("invalid layout")) };
+    assert_eq!(slab.utilization()[5].used, 0, "invalid utilization");
+
+    // Serve overaligned layouts without the slabs
+    let layout = Layout::from_size_align(16, 64).expect("invalid layout");
+    let block = unsafe { GlobalAlloc::alloc(&slab, layout) };
+    assert!(!block.is_null() && (block as usize).is_multiple_of(64), "invalid overaligned block");
+    unsafe { block.write_bytes(7, 16) };
+    let block = unsafe { GlobalAlloc::realloc(&slab, block, layout, 32) };
+    assert!(!block.is_null() && (block as usize).is_multiple_of(64), "invalid overaligned block");
+    assert_eq!(unsafe { *block.add(15) }, 7, "invalid block contents");
+    unsafe { GlobalAlloc::dealloc(&slab, block, Layout::from_size_align(32, 64).expect("invalid layout")) };
+    assert!(slab.utilization().iter().all(|class| class.used == 0), "invalid utilization");
+}
+
+#[cfg(feature = "slab")]
+pub fn slab_backend() {
+    use picosdk_malloc::{trace, Heap};
+
+    // Ensure that heap objects are served by the slab backend
+    let used = unsafe { trace::slab_utilization() }[2].used;
+    let heap = Heap::new(*b"Testolope Testolope").expect("failed to allocate memory");
+    assert_eq!(unsafe { trace::slab_utilization() }[2].used, used + 1, "invalid utilization");
+    drop(heap);
+    assert_eq!(unsafe { trace::slab_utilization() }[2].used, used, "invalid utilization");
+}
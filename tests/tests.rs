@@ -1,10 +1,46 @@
 #[cfg(not(feature = "trace"))]
 compile_error!("Tests require feature `trace`");
 
+mod _dmabuffer;
 mod _heap;
+mod _heaparc;
+mod _heaparena;
+mod _heappool;
+mod _heapqueue;
 mod _heapref;
+mod _heaprefmut;
+mod _heapring;
+mod _heapslotmap;
+mod _heapstring;
+mod _slab;
 
-use picosdk_malloc::trace;
+use picosdk_malloc::{trace, RawLock};
+use std::{
+    cell::Cell,
+    sync::{Mutex, PoisonError},
+};
+
+/// A lock that uses a global std mutex as critical section
+pub struct StdLock;
+unsafe impl RawLock for StdLock {
+    fn critical_section<F, R>(f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        static MUTEX: Mutex<()> = Mutex::new(());
+        let _guard = MUTEX.lock().unwrap_or_else(PoisonError::into_inner);
+        f()
+    }
+}
+
+/// A testing struct that counts how often it has been dropped
+#[derive(Clone)]
+pub struct DropCounter<'a>(pub &'a Cell<usize>);
+impl Drop for DropCounter<'_> {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
 
 /// Runs all tests in sequential order
 #[test]
@@ -20,6 +56,32 @@ fn all_sequential() {
     _heap::assume_init();
     _heap::new_default();
     _heap::new_from_fn();
+    _heap::unsize();
+    _heap::heap_dyn();
+    _heap::heap_dyn_drop();
+    _heap::heap_dyn_fnmut();
+    _heap::compare();
+    _heap::hash();
+    _heap::display();
+    _heap::display_dyn();
+    _heap::borrow();
+    _heap::iterator();
+    _heap::iterator_dyn();
+    _heap::try_clone();
+    _heap::try_clone_slice();
+    _heap::try_clone_panic();
+    _heap::try_clone_error();
+    _heap::try_clone_clone();
+    _heap::try_clone_str();
+    _heap::try_from_iter();
+    _heap::try_from_iter_panic();
+    _heap::conversions();
+    _heap::replace_take();
+    _heap::map_in_place();
+    _heap::map_in_place_aligned();
+    _heap::alignment();
+    _heap::try_resize_with();
+    _heap::try_resize_with_panic();
 
     // HeapRef tests
     _heapref::heapref_new_from_heap();
@@ -29,12 +91,103 @@ fn all_sequential() {
     _heapref::heapref_weak();
     _heapref::heapref_downgrade();
     _heapref::heapref_try_unwrap_heap();
+    _heapref::heapref_try_unwrap_heap_weak();
     _heapref::heapref_try_unwrap();
+    _heapref::heapref_try_unwrap_value();
     _heapref::heapref_clone();
     _heapref::heaprefweak_strong();
     _heapref::heaprefweak_weak();
     _heapref::heaprefweak_upgrade();
     _heapref::heaprefweak_clone();
+    _heapref::heapref_compare();
+    _heapref::heapref_display();
+    _heapref::heapref_try_clone();
+    _heapref::heapref_get_mut();
+    _heapref::heapref_try_make_mut();
+    _heapref::heapref_try_new_cyclic();
+    _heapref::heapref_try_new_cyclic_panic();
+    _heapref::heaprefweak_new();
+    _heapref::heapref_into_raw();
+    _heapref::heapref_strong_count();
+    _heapref::heaprefweak_into_raw();
+    _heapref::heapref_ptr_eq();
+    _heapref::heaprefweak_ptr_eq();
+    _heapref::heapref_into_inner();
+    _heapref::heapref_unwrap_or_clone();
+    _heapref::heapref_try_clone_ref();
+    #[cfg(feature = "refcount-u16")]
+    _heapref::heapref_overflow();
+    _heapref::uniqueheapref_new();
+    _heapref::uniqueheapref_into_shared();
+    _heapref::heapref_slice();
+    _heapref::heapref_slice_from_iter();
+    _heapref::heapref_str();
+    _heapref::heapref_try_from_iter();
+
+    // HeapRefMut tests
+    _heaprefmut::heaprefmut_new();
+    _heaprefmut::heaprefmut_borrow();
+    _heaprefmut::heaprefmut_debug();
+    _heaprefmut::heaprefmut_weak();
+    _heaprefmut::heaprefmut_get_mut();
+
+    // HeapRing tests
+    _heapring::heapring_push_pop();
+    _heapring::heapring_batch();
+    _heapring::heapring_drop();
+    _heapring::heapring_try_clone();
+
+    // HeapSlotMap tests
+    _heapslotmap::heapslotmap_insert_remove();
+    _heapslotmap::heapslotmap_retain();
+    _heapslotmap::heapslotmap_retire();
+    _heapslotmap::heapslotmap_drop();
+    _heapslotmap::heapslotmap_try_clone();
+
+    // HeapString tests
+    _heapstring::heapstring_push();
+    _heapstring::heapstring_write();
+    _heapstring::heapstring_convert();
+
+    // HeapArena tests
+    _heaparena::heaparena_alloc();
+    _heaparena::heaparena_reset();
+    _heaparena::heaparena_growable();
+
+    // HeapPool tests
+    _heappool::heappool_alloc();
+    _heappool::heappool_drop();
+    _heappool::heappool_growable();
+
+    // HeapQueue tests
+    _heapqueue::heapqueue_push_pop();
+    _heapqueue::heapqueue_drop();
+    _heapqueue::heapqueue_wrap();
+    _heapqueue::heapqueue_threads();
+
+    // HeapArc tests
+    _heaparc::heaparc_new();
+    _heaparc::heaparc_clone_downgrade();
+    _heaparc::heaparc_try_unwrap();
+    _heaparc::heaparc_try_make_mut();
+    _heaparc::heaparc_try_new_cyclic();
+    _heaparc::heaparc_into_raw();
+    _heaparc::heaparc_threads();
+
+    // DmaBuffer tests
+    _dmabuffer::dmabuffer_ring();
+    _dmabuffer::dmabuffer_no_ring();
+    _dmabuffer::heap_new_aligned();
+    _dmabuffer::dmabuffer_try_clone();
+
+    // Slab allocator tests
+    _slab::slab_alloc();
+    _slab::slab_realloc();
+    _slab::slab_directory();
+    _slab::slab_threads();
+    _slab::slab_global_alloc();
+    #[cfg(feature = "slab")]
+    _slab::slab_backend();
 
     // Ensure that we have not leaked memory
     assert_eq!(unsafe { trace::allocated() }, 0, "invalid amount of allocated bytes");